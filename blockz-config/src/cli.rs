@@ -0,0 +1,363 @@
+//! Command-line arguments that set the values of app configurations.
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Write;
+
+use figment::value::Dict;
+use figment::value::Map;
+use figment::value::Value;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+
+use crate::env;
+use crate::field;
+use crate::field::Literal;
+use crate::suggest;
+use crate::AppConfig;
+use crate::Field;
+use crate::PROFILE_KEY;
+
+/// An error in the command-line arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArgsError {
+    /// Help was requested with `--help` or `-h`; this holds the help text.
+    Help(String),
+    /// A flag does not name a field.
+    Unknown {
+        /// The flag, e.g. `--prot`.
+        flag: String,
+        /// The closest flag that names a field, if there is one.
+        suggestion: Option<String>,
+    },
+    /// A flag is missing its value.
+    MissingValue(String),
+    /// An argument is not a flag.
+    Unexpected(String),
+}
+
+impl Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Help(help) => f.write_str(help),
+            Self::Unknown {
+                flag,
+                suggestion: Some(suggestion),
+            } => write!(f, "unknown flag `{}`, did you mean `{}`?", flag, suggestion),
+            Self::Unknown { flag, .. } => write!(f, "unknown flag `{}`", flag),
+            Self::MissingValue(flag) => write!(f, "flag `{}` is missing its value", flag),
+            Self::Unexpected(arg) => write!(f, "unexpected argument `{}`", arg),
+        }
+    }
+}
+
+impl Error for ArgsError {}
+
+/// A provider for the values given as command-line flags.
+///
+/// Every key path is a flag: `--server.port 8080` or `--server.port=8080`
+/// sets `server.port`, and dashes in flags stand for underscores in keys.
+/// Boolean fields may be given without a value to set them to `true`. Lists
+/// are read as in the environment: fields declared as lists take
+/// comma-separated items, e.g. `--hosts a,b`.
+///
+/// The `--profile` flag selects the active profile, unless a field is named
+/// `profile`. If the configuration declares its [fields](AppConfig::fields),
+/// flags that do not name one are rejected.
+pub(crate) struct Args {
+    values: Vec<(String, Value)>,
+    profile: Option<String>,
+}
+
+impl Args {
+    /// Parse the command-line arguments of `T`, without the program name.
+    pub(crate) fn parse<T: AppConfig>(args: &[String]) -> Result<Self, ArgsError> {
+        let fields = T::fields();
+        let paths = field::paths(&fields);
+
+        let mut values = Vec::new();
+        let mut profile = None;
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(ArgsError::Help(help::<T>()));
+            }
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) if !flag.is_empty() => flag,
+                _ => return Err(ArgsError::Unexpected(arg.clone())),
+            };
+
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let path = name.replace('-', "_");
+            if path == PROFILE_KEY && !paths.contains(&path) {
+                profile = match value.or_else(|| args.next().cloned()) {
+                    Some(value) => Some(value),
+                    None => return Err(ArgsError::MissingValue(format!("--{}", name))),
+                };
+                continue;
+            }
+            if !paths.is_empty() && !paths.contains(&path) {
+                let suggestion = suggest::closest(&path, paths.iter().map(String::as_str));
+                return Err(ArgsError::Unknown {
+                    flag: format!("--{}", name),
+                    suggestion: suggestion.map(flag_name),
+                });
+            }
+
+            let is_bool = field::find(&fields, &path).is_some_and(|field| field.ty == "bool");
+            let value = match value {
+                Some(value) => value,
+                None if is_bool => match args.peek() {
+                    Some(next) if *next == "true" || *next == "false" => {
+                        args.next().unwrap().clone()
+                    }
+                    _ => "true".to_string(),
+                },
+                None => match args.next() {
+                    Some(value) if !value.starts_with("--") => value.clone(),
+                    _ => return Err(ArgsError::MissingValue(format!("--{}", name))),
+                },
+            };
+            values.push((path, value.parse().expect("parsing a value is infallible")));
+        }
+
+        Ok(Self { values, profile })
+    }
+
+    /// Get the profile selected with `--profile`, if any.
+    pub(crate) fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+}
+
+impl Provider for Args {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("command line")
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        let mut dict = Dict::new();
+        for (path, value) in &self.values {
+            field::insert(&mut dict, path, value.clone());
+        }
+        Ok(Profile::Global.collect(dict))
+    }
+}
+
+/// Get the flag that sets a key path.
+pub(crate) fn flag_name(path: &str) -> String {
+    format!("--{}", path.replace('_', "-"))
+}
+
+/// Get the help text for the command-line flags of `T`.
+pub fn help<T: AppConfig>() -> String {
+    fn walk<T: AppConfig>(fields: &[Field], prefix: &str, help: &mut String) {
+        for field in fields {
+            let path = format!("{}{}", prefix, field.name);
+            if field.is_section() {
+                walk::<T>(&field.fields, &format!("{}.", path), help);
+                continue;
+            }
+
+            let _ = writeln!(help, "  {} <{}>", flag_name(&path), field.ty);
+            for line in field.doc().lines() {
+                let _ = writeln!(help, "          {}", line);
+            }
+
+            let mut notes = Vec::new();
+            match &field.default {
+                Some(_) if field.is_secret() => notes.push("[default: <redacted>]".to_string()),
+                Some(default) => notes.push(format!("[default: {}]", Literal(default))),
+                None => {}
+            }
+            notes.push(format!("[env: {}]", env::env_var::<T>(&path)));
+            if let Some(env) = field.env {
+                notes.push(format!("[env: {}]", env));
+            }
+            let _ = writeln!(help, "          {}", notes.join(" "));
+        }
+    }
+
+    let fields = T::fields();
+    let mut help = String::from("Options:\n");
+    walk::<T>(&fields, "", &mut help);
+    if field::find(&fields, PROFILE_KEY).is_none() {
+        let _ = writeln!(
+            help,
+            "  --profile <name>\n          Select the profile of the config file\n          [env: {}]",
+            env::env_var::<T>(PROFILE_KEY)
+        );
+    }
+    help.push_str("  -h, --help\n          Print help\n");
+    help
+}
+
+#[cfg(test)]
+mod test {
+    use figment::value::Value;
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::help;
+    use super::ArgsError;
+    use crate::AppConfig;
+    use crate::Field;
+    use crate::Layer;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        pool_size: u32,
+        debug: bool,
+        server: Server,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Server {
+        port: u16,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("pool_size", "u32")
+                    .with_docs(&[" The size of the pool."])
+                    .with_default(Value::from(4)),
+                Field::new("debug", "bool").with_default(Value::from(false)),
+                Field::new("server", "Server").with_fields(vec![Field::new("port", "u16")]),
+            ]
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cli_precedence() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_POOL_SIZE", 8);
+            jail.set_env("APP_SERVER__PORT", 80);
+
+            let config = TestConfig::loader()
+                .args(args(&[
+                    "--server.port",
+                    "8080",
+                    "--debug",
+                    "--pool-size=16",
+                ]))
+                .load()
+                .unwrap();
+            assert_eq!(config.server.port, 8080);
+            assert_eq!(config.pool_size, 16);
+            assert!(config.debug);
+
+            let config = TestConfig::loader()
+                .args(args(&["--debug", "false"]))
+                .set("pool_size", 32)
+                .load()
+                .unwrap();
+            assert!(!config.debug);
+            assert_eq!(config.pool_size, 32);
+
+            let err = TestConfig::loader()
+                .args(args(&["--server.port", "high"]))
+                .load()
+                .unwrap_err();
+            assert_eq!(err.layer(), Some(Layer::Args));
+            assert!(err.to_string().contains("--server.port"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_cli_errors() {
+        Jail::expect_with(|_| {
+            let load = |a: &[&str]| TestConfig::loader().args(args(a)).load().unwrap_err();
+
+            let err = load(&["--server.prot", "1"]);
+            assert_eq!(
+                err.args(),
+                Some(&ArgsError::Unknown {
+                    flag: "--server.prot".to_string(),
+                    suggestion: Some("--server.port".to_string()),
+                })
+            );
+            assert!(err.to_string().contains("did you mean `--server.port`?"));
+
+            let err = load(&["--server.port"]);
+            assert_eq!(
+                err.args(),
+                Some(&ArgsError::MissingValue("--server.port".to_string()))
+            );
+
+            let err = load(&["8080"]);
+            assert_eq!(err.args(), Some(&ArgsError::Unexpected("8080".to_string())));
+
+            let err = load(&["--debug", "--help"]);
+            assert_eq!(err.args(), Some(&ArgsError::Help(help::<TestConfig>())));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_cli_lists() {
+        #[derive(Debug, Deserialize)]
+        struct ListConfig {
+            hosts: Vec<String>,
+            ports: Vec<u16>,
+        }
+
+        impl AppConfig for ListConfig {
+            const PACKAGE: &'static str = "APP_";
+
+            fn fields() -> Vec<Field> {
+                vec![
+                    Field::new("hosts", "Vec<String>"),
+                    Field::new("ports", "Vec<u16>"),
+                ]
+            }
+        }
+
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_HOSTS", "env");
+            jail.set_env("APP_PORTS", "80");
+
+            let config = ListConfig::loader()
+                .args(args(&["--hosts", "a, b", "--ports=8080,8081"]))
+                .load()
+                .unwrap();
+            assert_eq!(config.hosts, ["a", "b"]);
+            assert_eq!(config.ports, [8080, 8081]);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_cli_help() {
+        assert_eq!(
+            help::<TestConfig>(),
+            "Options:
+  --pool-size <u32>
+          The size of the pool.
+          [default: 4] [env: APP_POOL_SIZE]
+  --debug <bool>
+          [default: false] [env: APP_DEBUG]
+  --server.port <u16>
+          [env: APP_SERVER__PORT]
+  --profile <name>
+          Select the profile of the config file
+          [env: APP_PROFILE]
+  -h, --help
+          Print help
+"
+        );
+    }
+}
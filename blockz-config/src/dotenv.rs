@@ -0,0 +1,326 @@
+//! `.env` files that set environment variables for local development.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use figment::value::Dict;
+use figment::value::Map;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+
+use crate::env;
+use crate::field;
+use crate::loader::CONFIG_FILE_KEY;
+use crate::loader::PROFILE_KEY;
+use crate::secret;
+use crate::AppConfig;
+
+/// A provider for the variables of env files that set fields.
+///
+/// Variables are read like environment variables: those under the prefix,
+/// and those named by the `#[env]` attributes of fields. The config file and
+/// profile variables, and the `_FILE` variables of declared fields, are
+/// ignored: env files do not name secret files. The values apply to every
+/// profile.
+#[derive(Clone)]
+pub(crate) struct DotEnv {
+    values: Dict,
+    origins: Vec<(String, String)>,
+}
+
+impl DotEnv {
+    /// Read the env files at `paths`, each overriding the ones before it, or
+    /// fail with a message.
+    pub(crate) fn read<T: AppConfig>(paths: &[PathBuf]) -> Result<Self, String> {
+        let fields = T::fields();
+        let paths_of_fields = field::paths(&fields);
+        let aliases = field::env_vars(&fields);
+
+        let mut dotenv = Self {
+            values: Dict::new(),
+            origins: Vec::new(),
+        };
+        for file in paths {
+            for (var, value) in read(file)? {
+                let path = aliases
+                    .iter()
+                    .find(|(alias, _)| alias.eq_ignore_ascii_case(&var))
+                    .map(|(_, path)| path.clone())
+                    .or_else(|| env::key_path::<T>(&var))
+                    .filter(|path| {
+                        path != CONFIG_FILE_KEY
+                            && path != PROFILE_KEY
+                            && secret::declared_file_key(path, &paths_of_fields).is_none()
+                    });
+                if let Some(path) = path {
+                    let value = value.parse().expect("parsing a value is infallible");
+                    field::insert(&mut dotenv.values, &path, value);
+                    let origin = format!("{} in {}", var, file.display());
+                    dotenv.origins.retain(|(other, _)| *other != path);
+                    dotenv.origins.push((path, origin));
+                }
+            }
+        }
+        Ok(dotenv)
+    }
+
+    /// Returns whether no variable sets a field.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Get the variable and the file that set a key path, e.g.
+    /// `APP_PORT in .env`.
+    pub(crate) fn origin(&self, path: &str) -> Option<&str> {
+        self.origins
+            .iter()
+            .find(|(other, _)| other == path)
+            .map(|(_, origin)| origin.as_str())
+    }
+}
+
+impl Provider for DotEnv {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("env file")
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        Ok(Profile::Global.collect(self.values.clone()))
+    }
+}
+
+/// Read the variables of the env file at `path`, in order.
+fn read(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read the env file {}: {}", path.display(), e))?;
+    parse(&text).map_err(|(line, message)| {
+        format!("invalid env file {}:{}: {}", path.display(), line, message)
+    })
+}
+
+/// Parse the variables of an env file, or fail with a line number and a
+/// message.
+///
+/// Every line is a `NAME=value` pair, optionally preceded by `export`. A
+/// single-quoted value is taken literally, and a double-quoted value may hold
+/// the escapes `\n`, `\r`, `\t`, `\"` and `\\`; both may span lines.
+/// `#` starts a comment on its own line, after a quoted value, or after
+/// whitespace in an unquoted value.
+fn parse(text: &str) -> Result<Vec<(String, String)>, (usize, String)> {
+    let mut vars = Vec::new();
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = match line.strip_prefix("export") {
+            Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+            _ => line,
+        };
+
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| (number, "expected `NAME=value`".to_string()))?;
+        let name = name.trim();
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if !valid {
+            return Err((number, format!("invalid variable name `{}`", name)));
+        }
+
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                let mut quoted = value[1..].to_string();
+                loop {
+                    if let Some((value, rest)) = unquote(&quoted, quote) {
+                        let rest = rest.trim_start();
+                        if !rest.is_empty() && !rest.starts_with('#') {
+                            return Err((number, "unexpected text after a quoted value".into()));
+                        }
+                        break value;
+                    }
+                    match lines.next() {
+                        Some((_, line)) => {
+                            quoted.push('\n');
+                            quoted.push_str(line);
+                        }
+                        None => return Err((number, "unterminated quoted value".into())),
+                    }
+                }
+            }
+            _ => {
+                let end = value
+                    .char_indices()
+                    .find(|&(i, c)| c == '#' && value[..i].ends_with(char::is_whitespace))
+                    .map_or(value.len(), |(i, _)| i);
+                value[..end].trim_end().to_string()
+            }
+        };
+        vars.push((name.to_string(), value));
+    }
+    Ok(vars)
+}
+
+/// Split a value that starts after an opening `quote` at its closing quote,
+/// if it has one.
+fn unquote(s: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((value, &s[i + 1..])),
+            '\\' if quote == '"' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                c @ '"' | c @ '\\' => value.push(c),
+                c => {
+                    value.push('\\');
+                    value.push(c);
+                }
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::parse;
+    use crate::AppConfig;
+    use crate::Field;
+    use crate::Layer;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        port: u16,
+        host: String,
+        peers: Vec<String>,
+        url: String,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("port", "u16"),
+                Field::new("host", "String"),
+                Field::new("peers", "Vec<String>"),
+                Field::new("url", "String").with_env("DATABASE_URL"),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+            # a comment
+            export APP_PORT=8080
+            APP_HOST = localhost # a comment
+            APP_URL=a#b
+            SINGLE='no \n escapes, # or comments'
+            DOUBLE="line\n\"quoted\"" # a comment
+            MULTI="first
+            second"
+            EMPTY=
+        "#;
+        let vars = parse(text).unwrap();
+        let vars: Vec<(&str, &str)> = vars
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            vars,
+            [
+                ("APP_PORT", "8080"),
+                ("APP_HOST", "localhost"),
+                ("APP_URL", "a#b"),
+                ("SINGLE", "no \\n escapes, # or comments"),
+                ("DOUBLE", "line\n\"quoted\""),
+                ("MULTI", "first\n            second"),
+                ("EMPTY", ""),
+            ]
+        );
+
+        assert_eq!(parse("A=1\nB='open\n").unwrap_err().0, 2);
+        assert_eq!(parse("A=\"x\" y").unwrap_err().0, 1);
+        assert_eq!(parse("\nexport\n").unwrap_err().0, 2);
+        assert_eq!(parse("A B=1").unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn test_dotenv() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                ".env",
+                "APP_PORT=8080\nAPP_HOST=dotenv\nAPP_PEERS=a,b\nDATABASE_URL=postgres://db\nAPP_PROFILE=x",
+            )?;
+            jail.set_env("APP_HOST", "env");
+
+            let config = TestConfig::load().unwrap();
+            assert_eq!(config.port, 8080);
+            assert_eq!(config.host, "env");
+            assert_eq!(config.peers, ["a", "b"]);
+            assert_eq!(config.url, "postgres://db");
+
+            let explanation = TestConfig::explain().unwrap();
+            let port = explanation.get("port").unwrap();
+            assert_eq!(port.layer(), Some(Layer::Env));
+            assert_eq!(port.origin(), Some("APP_PORT in .env"));
+
+            let config = TestConfig::loader().dotenv_override(true).load().unwrap();
+            assert_eq!(config.host, "dotenv");
+
+            jail.create_file("local.env", "APP_PORT=9090")?;
+            let config = TestConfig::loader()
+                .dotenv(".env")
+                .dotenv("local.env")
+                .load()
+                .unwrap();
+            assert_eq!(config.port, 9090);
+            assert_eq!(config.host, "env");
+
+            let err = TestConfig::loader()
+                .dotenv("missing.env")
+                .load()
+                .unwrap_err();
+            assert!(err.to_string().contains("failed to read the env file"));
+
+            jail.create_file("bad.env", "APP_PORT='8080")?;
+            let err = TestConfig::loader().dotenv("bad.env").load().unwrap_err();
+            assert!(err.to_string().contains("invalid env file bad.env:1"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_dotenv_production() {
+        Jail::expect_with(|jail| {
+            jail.create_file(".env", "APP_PORT=8080\nAPP_HOST=dotenv\nAPP_PEERS=")?;
+            jail.set_env("DATABASE_URL", "postgres://db");
+
+            jail.set_env("APP_PROFILE", "Prod");
+            let err = TestConfig::load().unwrap_err();
+            assert!(err.to_string().contains("missing field `port`"));
+
+            let config = TestConfig::loader().dotenv(".env").load().unwrap();
+            assert_eq!(config.port, 8080);
+
+            let config = TestConfig::loader().profile("dev").load().unwrap();
+            assert_eq!(config.port, 8080);
+            assert!(config.peers.is_empty());
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,320 @@
+//! Environment variables that set the values of app configurations.
+
+use figment::providers::Env;
+use figment::value::Dict;
+use figment::value::Map;
+use figment::value::Value;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+
+use crate::field;
+use crate::AppConfig;
+use crate::Field;
+
+/// Get the environment variable prefix of `T`.
+///
+/// This is [`ENV_PREFIX`](AppConfig::ENV_PREFIX) if it is set, or else the
+/// [`PACKAGE`](AppConfig::PACKAGE) normalised with [`normalize_prefix`].
+pub fn env_prefix<T: AppConfig>() -> String {
+    match T::ENV_PREFIX {
+        Some(prefix) => prefix.to_string(),
+        None => normalize_prefix(T::PACKAGE),
+    }
+}
+
+/// Normalise a package name into a prefix that can be set from shells.
+///
+/// The name is uppercased, `-` and `.` are replaced with `_` and a trailing
+/// `_` is added, e.g. `blockz-config` becomes `BLOCKZ_CONFIG_`.
+pub fn normalize_prefix(package: &str) -> String {
+    let mut prefix: String = package
+        .chars()
+        .map(|c| match c {
+            '-' | '.' => '_',
+            c => c.to_ascii_uppercase(),
+        })
+        .collect();
+    if !prefix.is_empty() && !prefix.ends_with('_') {
+        prefix.push('_');
+    }
+    prefix
+}
+
+/// Get the environment variable under the prefix of `T` that sets a key
+/// path.
+///
+/// The keys of the path are joined with the
+/// [`ENV_SEPARATOR`](AppConfig::ENV_SEPARATOR), e.g. `database.pool.size` is
+/// set by `APP_DATABASE__POOL__SIZE`. This is the inverse of [`key_path`].
+pub fn env_var<T: AppConfig>(path: &str) -> String {
+    let keys: Vec<&str> = path.split('.').collect();
+    format!("{}{}", env_prefix::<T>(), keys.join(T::ENV_SEPARATOR)).to_ascii_uppercase()
+}
+
+/// Get the key path set by an environment variable under the prefix of `T`,
+/// if it is under the prefix.
+///
+/// This is the inverse of [`env_var`].
+pub fn key_path<T: AppConfig>(var: &str) -> Option<String> {
+    let prefix = env_prefix::<T>().to_ascii_uppercase();
+    let var = var.to_ascii_uppercase();
+    let key = var.strip_prefix(&prefix).filter(|key| !key.is_empty())?;
+    let keys: Vec<&str> = key.split(&T::ENV_SEPARATOR.to_ascii_uppercase()).collect();
+    Some(keys.join(".").to_ascii_lowercase())
+}
+
+/// Get the environment variables under the prefix of `T`.
+///
+/// Both the [`ENV_SEPARATOR`](AppConfig::ENV_SEPARATOR) and dots nest keys.
+pub(crate) fn prefixed<T: AppConfig>() -> Env {
+    Env::prefixed(&env_prefix::<T>()).split(T::ENV_SEPARATOR)
+}
+
+/// A provider for environment variables, or for other flat values such as
+/// command-line flags, that reads lists.
+///
+/// A list is either set by one variable, with its items separated by commas,
+/// e.g. `APP_PEERS=a,b`, or by one variable per item, suffixed with its index,
+/// e.g. `APP_PEERS__0=a`. Items are separated by commas only for fields that
+/// are declared as lists.
+///
+/// Variables suffixed with indices set a list if the field is declared as a
+/// list, in which case the indices must start at 0 without gaps. Otherwise,
+/// they set a list only if the field is not declared and the indices start at
+/// 0 without gaps, so that maps keyed by numbers, e.g. `APP_PORTS__80=http`,
+/// are kept.
+pub(crate) struct EnvVars<P = Env> {
+    env: P,
+    lists: Vec<String>,
+    leaves: Vec<String>,
+}
+
+impl<P: Provider> EnvVars<P> {
+    /// Create a new `EnvVars` for the variables of `env`.
+    pub(crate) fn new(env: P, fields: &[Field]) -> Self {
+        let leaves = field::paths(fields);
+        let lists = leaves
+            .iter()
+            .filter(|path| field::find(fields, path).is_some_and(Field::is_list))
+            .cloned()
+            .collect();
+        Self { env, lists, leaves }
+    }
+}
+
+impl<P: Provider> Provider for EnvVars<P> {
+    fn metadata(&self) -> Metadata {
+        self.env.metadata()
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        let mut data = self.env.data()?;
+        for dict in data.values_mut() {
+            read_lists(dict, "", &self.lists, &self.leaves).map_err(figment::Error::from)?;
+        }
+        Ok(data)
+    }
+}
+
+/// Read the lists in `dict`: the dicts keyed by indices, and the values of
+/// the `lists` paths, or fail with a message.
+fn read_lists(
+    dict: &mut Dict,
+    prefix: &str,
+    lists: &[String],
+    leaves: &[String],
+) -> Result<(), String> {
+    for (key, value) in dict.iter_mut() {
+        let path = format!("{}{}", prefix, key);
+        if let Value::Dict(_, inner) = value {
+            read_lists(inner, &format!("{}.", path), lists, leaves)?;
+        }
+
+        let list = lists.contains(&path);
+        if let Some(items) = indexed(value) {
+            let gap = items
+                .iter()
+                .enumerate()
+                .find(|(i, (index, _))| i != index)
+                .map(|(i, _)| i);
+            match gap {
+                Some(missing) if list => {
+                    return Err(format!("missing the item {} of `{}`", missing, path));
+                }
+                None if list || !leaves.contains(&path) => {
+                    let items: Vec<Value> = items.into_iter().map(|(_, value)| value).collect();
+                    *value = Value::from(items);
+                }
+                _ => {}
+            }
+        } else if list {
+            let items: Vec<Value> = match &*value {
+                Value::Array(..) => continue,
+                Value::String(_, s) if s.trim().is_empty() => Vec::new(),
+                Value::String(_, s) => s
+                    .split(',')
+                    .map(|item| item.trim().parse().expect("parsing a value is infallible"))
+                    .collect(),
+                value => vec![value.clone()],
+            };
+            *value = Value::from(items);
+        }
+    }
+    Ok(())
+}
+
+/// Get the items of a dict keyed by indices, with their indices, in order.
+fn indexed(value: &Value) -> Option<Vec<(usize, Value)>> {
+    let dict = value.as_dict().filter(|dict| !dict.is_empty())?;
+    let mut items: Vec<(usize, Value)> = dict
+        .iter()
+        .map(|(key, value)| Some((key.parse().ok()?, value.clone())))
+        .collect::<Option<_>>()?;
+    items.sort_by_key(|(index, _)| *index);
+    Some(items)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::env_prefix;
+    use super::env_var;
+    use super::key_path;
+    use super::normalize_prefix;
+    use crate::AppConfig;
+    use crate::Field;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        peers: Vec<String>,
+        ports: Vec<u16>,
+        database: Database,
+        #[serde(default)]
+        listeners: HashMap<String, String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Database {
+        pool_size: u32,
+        replicas: Vec<String>,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("peers", "Vec<String>"),
+                Field::new("ports", "Vec<u16>"),
+                Field::new("database", "Database").with_fields(vec![
+                    Field::new("pool_size", "u32"),
+                    Field::new("replicas", "Vec<String>"),
+                ]),
+                Field::new("listeners", "HashMap<String, String>"),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_env_round_trip() {
+        for path in ["port", "database.pool_size", "a.b.c"] {
+            let var = env_var::<TestConfig>(path);
+            assert_eq!(key_path::<TestConfig>(&var).as_deref(), Some(path));
+        }
+        assert_eq!(
+            env_var::<TestConfig>("database.pool_size"),
+            "APP_DATABASE__POOL_SIZE"
+        );
+        assert_eq!(key_path::<TestConfig>("OTHER_PORT"), None);
+    }
+
+    #[test]
+    fn test_env_prefix() {
+        assert_eq!(normalize_prefix("blockz-config"), "BLOCKZ_CONFIG_");
+        assert_eq!(normalize_prefix("app.v2"), "APP_V2_");
+        assert_eq!(normalize_prefix("APP_"), "APP_");
+        assert_eq!(normalize_prefix(""), "");
+
+        #[derive(Debug, Deserialize)]
+        struct Prefixed {}
+
+        impl AppConfig for Prefixed {
+            const PACKAGE: &'static str = "my-app";
+            const ENV_PREFIX: Option<&'static str> = Some("MINE__");
+        }
+
+        assert_eq!(env_prefix::<Prefixed>(), "MINE__");
+        assert_eq!(env_var::<Prefixed>("port"), "MINE__PORT");
+        assert_eq!(key_path::<Prefixed>("MINE__PORT").as_deref(), Some("port"));
+    }
+
+    #[test]
+    fn test_env_nested_lists() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_PEERS", "a, b,c");
+            jail.set_env("APP_PORTS", "80");
+            jail.set_env("APP_DATABASE__POOL_SIZE", 8);
+            jail.set_env("APP_DATABASE__REPLICAS__1", "second");
+            jail.set_env("APP_DATABASE__REPLICAS__0", "first");
+
+            let config = TestConfig::load().unwrap();
+            assert_eq!(config.peers, ["a", "b", "c"]);
+            assert_eq!(config.ports, [80]);
+            assert_eq!(config.database.pool_size, 8);
+            assert_eq!(config.database.replicas, ["first", "second"]);
+
+            jail.set_env("APP_DATABASE__POOL_SIZE", "many");
+            let err = TestConfig::load().unwrap_err();
+            assert!(err.to_string().contains("APP_DATABASE__POOL_SIZE"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_env_indexed_maps() {
+        #[derive(Debug, Deserialize)]
+        struct UndeclaredConfig {
+            peers: Vec<String>,
+            ports: HashMap<String, String>,
+        }
+
+        impl AppConfig for UndeclaredConfig {
+            const PACKAGE: &'static str = "APP_";
+        }
+
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_PEERS", "a");
+            jail.set_env("APP_PORTS", "80");
+            jail.set_env("APP_DATABASE__POOL_SIZE", 8);
+            jail.set_env("APP_DATABASE__REPLICAS", "");
+            jail.set_env("APP_LISTENERS__0", "http");
+            jail.set_env("APP_LISTENERS__1", "https");
+
+            let config = TestConfig::load().unwrap();
+            assert_eq!(config.listeners["0"], "http");
+            assert_eq!(config.listeners["1"], "https");
+
+            jail.set_env("APP_DATABASE__REPLICAS__0", "first");
+            jail.set_env("APP_DATABASE__REPLICAS__5", "sixth");
+            let err = TestConfig::load().unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("missing the item 1 of `database.replicas`"));
+
+            jail.set_env("APP_PEERS__0", "a");
+            jail.set_env("APP_PEERS__1", "b");
+            jail.set_env("APP_PORTS__80", "http");
+            jail.set_env("APP_PORTS__443", "https");
+            let config = UndeclaredConfig::load().unwrap();
+            assert_eq!(config.peers, ["a", "b"]);
+            assert_eq!(config.ports["80"], "http");
+            assert_eq!(config.ports["443"], "https");
+            Ok(())
+        });
+    }
+}
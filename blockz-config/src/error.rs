@@ -0,0 +1,206 @@
+//! Errors returned while loading app configurations.
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+
+use crate::AppConfig;
+use crate::ArgsError;
+use crate::InterpolationError;
+use crate::Issue;
+use crate::Layer;
+use crate::Violation;
+
+/// Error that can be returned by the `AppConfig` trait.
+#[derive(Debug)]
+pub struct AppConfigError<T> {
+    repr: Repr,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+#[derive(Debug)]
+enum Repr {
+    /// The configuration could not be extracted.
+    Extract(Box<figment::Error>),
+    /// The configuration could not be extracted because of these keys.
+    Keys(Vec<Issue>, Box<figment::Error>),
+    /// The configuration was extracted but has keys that do not map to any
+    /// field.
+    Unknown(Vec<Issue>),
+    /// The configuration was extracted but has invalid values.
+    Invalid(Vec<Violation>),
+    /// The command-line arguments could not be parsed.
+    Args(ArgsError),
+    /// The references inside the values could not be resolved.
+    Interpolate(InterpolationError),
+}
+
+impl<T> AppConfigError<T> {
+    pub(crate) fn new(inner: figment::Error) -> Self {
+        Self {
+            repr: Repr::Extract(Box::new(inner)),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub(crate) fn from_issues(issues: Vec<Issue>, inner: figment::Error) -> Self {
+        Self {
+            repr: Repr::Keys(issues, Box::new(inner)),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub(crate) fn from_unknown(issues: Vec<Issue>) -> Self {
+        Self {
+            repr: Repr::Unknown(issues),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub(crate) fn invalid(violations: Vec<Violation>) -> Self {
+        Self {
+            repr: Repr::Invalid(violations),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub(crate) fn from_args(inner: ArgsError) -> Self {
+        Self {
+            repr: Repr::Args(inner),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub(crate) fn from_interpolation(inner: InterpolationError) -> Self {
+        Self {
+            repr: Repr::Interpolate(inner),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Get the layer that supplied the offending value, if it is known.
+    ///
+    /// If several values are invalid, this is the layer of the first one
+    /// whose layer is known.
+    pub fn layer(&self) -> Option<Layer> {
+        match &self.repr {
+            Repr::Extract(inner) => {
+                let metadata = inner.metadata.as_ref()?;
+                Layer::from_name(&metadata.name)
+            }
+            Repr::Keys(issues, _) | Repr::Unknown(issues) => issues.iter().find_map(Issue::layer),
+            Repr::Invalid(violations) => violations.first()?.layer(),
+            Repr::Args(_) => Some(Layer::Args),
+            Repr::Interpolate(_) => None,
+        }
+    }
+
+    /// Get the keys that are missing or hold values of the wrong type, or,
+    /// in [strict](crate::AppConfig::STRICT) mode, that do not map to any
+    /// field.
+    ///
+    /// This is empty if the configuration was extracted without unknown
+    /// keys, or could not be for another reason, such as a config file that
+    /// cannot be parsed.
+    pub fn issues(&self) -> &[Issue] {
+        match &self.repr {
+            Repr::Keys(issues, _) | Repr::Unknown(issues) => issues,
+            _ => &[],
+        }
+    }
+
+    /// Get the values that failed validation.
+    ///
+    /// This is empty if the configuration could not be extracted.
+    pub fn violations(&self) -> &[Violation] {
+        match &self.repr {
+            Repr::Invalid(violations) => violations,
+            _ => &[],
+        }
+    }
+
+    /// Get the error in the command-line arguments, if there is one.
+    ///
+    /// This is how a request for help is reported, see [`ArgsError::Help`].
+    pub fn args(&self) -> Option<&ArgsError> {
+        match &self.repr {
+            Repr::Args(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    /// Get the error in the references inside the values, if there is one.
+    pub fn interpolation(&self) -> Option<&InterpolationError> {
+        match &self.repr {
+            Repr::Interpolate(inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+impl<T: AppConfig> Display for AppConfigError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Extract(inner) if f.alternate() => f.write_fmt(format_args!(
+                "{}: app config error: {:#}",
+                T::PACKAGE,
+                inner
+            )),
+            Repr::Extract(inner) => {
+                f.write_fmt(format_args!("{}: app config error: {}", T::PACKAGE, inner))
+            }
+            Repr::Keys(issues, _) => {
+                f.write_fmt(format_args!(
+                    "{}: app config error: {} missing or invalid key(s)",
+                    T::PACKAGE,
+                    issues.len()
+                ))?;
+                for issue in issues {
+                    f.write_fmt(format_args!("\n  {}", issue))?;
+                }
+                Ok(())
+            }
+            Repr::Unknown(issues) => {
+                f.write_fmt(format_args!(
+                    "{}: app config error: {} unknown key(s)",
+                    T::PACKAGE,
+                    issues.len()
+                ))?;
+                for issue in issues {
+                    f.write_fmt(format_args!("\n  {}", issue))?;
+                }
+                Ok(())
+            }
+            Repr::Invalid(violations) => {
+                f.write_fmt(format_args!(
+                    "{}: app config error: {} invalid value(s)",
+                    T::PACKAGE,
+                    violations.len()
+                ))?;
+                for violation in violations {
+                    f.write_fmt(format_args!("\n  {}", violation))?;
+                }
+                Ok(())
+            }
+            // the help is meant to be printed as is
+            Repr::Args(ArgsError::Help(help)) => f.write_str(help),
+            Repr::Args(inner) => {
+                f.write_fmt(format_args!("{}: app config error: {}", T::PACKAGE, inner))
+            }
+            Repr::Interpolate(inner) => {
+                f.write_fmt(format_args!("{}: app config error: {}", T::PACKAGE, inner))
+            }
+        }
+    }
+}
+
+impl<T: AppConfig> Error for AppConfigError<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.repr {
+            Repr::Extract(inner) | Repr::Keys(_, inner) => Some(&**inner),
+            Repr::Unknown(_) | Repr::Invalid(_) => None,
+            Repr::Args(inner) => Some(inner),
+            Repr::Interpolate(inner) => Some(inner),
+        }
+    }
+}
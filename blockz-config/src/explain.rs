@@ -0,0 +1,332 @@
+//! Explanations of where the values of app configurations come from.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use figment::value::Dict;
+use figment::value::Value;
+use figment::Figment;
+use figment::Source;
+
+use crate::field;
+use crate::field::Literal;
+use crate::secret::FILE_SUFFIX;
+use crate::AppConfigError;
+use crate::Field;
+use crate::Layer;
+use crate::Result;
+
+/// What is shown instead of a secret value.
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// Where an effective value of a configuration came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    path: String,
+    value: String,
+    layer: Option<Layer>,
+    origin: Option<String>,
+}
+
+impl Entry {
+    /// Get the key path of the value.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the value, as an inline TOML value, or `<redacted>` for secrets and
+    /// for keys without a declared field.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Get the layer that supplied the value, if it is known.
+    pub fn layer(&self) -> Option<Layer> {
+        self.layer
+    }
+
+    /// Get the origin of the value within its layer, if it is known.
+    ///
+    /// This is the path of the config file with the line of the key, the
+    /// environment variable or the command-line flag.
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.path, self.value)?;
+        match (self.layer, &self.origin) {
+            (Some(layer), Some(origin)) => write!(f, "  # {}: {}", layer, origin),
+            (Some(layer), None) => write!(f, "  # {}", layer),
+            (None, Some(origin)) => write!(f, "  # {}", origin),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// A report of every effective value of a configuration and where it came
+/// from.
+///
+/// The report is printed with one key per line, e.g.
+/// `port = 8080  # environment: APP_PORT`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation {
+    entries: Vec<Entry>,
+}
+
+impl Explanation {
+    /// Explain the values in `figment`.
+    pub(crate) fn new<T>(figment: &Figment, fields: &[Field]) -> Result<Self, T> {
+        let values: Dict = figment.extract().map_err(AppConfigError::new)?;
+        let mut paths = Vec::new();
+        leaves(&values, "", &mut paths);
+
+        let mut files = Files::default();
+        let entries = paths
+            .into_iter()
+            .map(|path| explain(figment, fields, &mut files, path))
+            .collect();
+        Ok(Self { entries })
+    }
+
+    /// Get the entries, in the order of their key paths.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Get the entry of a key path.
+    pub fn get(&self, path: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the key paths of the values in `dict` that are not dicts.
+fn leaves(dict: &Dict, prefix: &str, paths: &mut Vec<String>) {
+    for (key, value) in dict {
+        let path = format!("{}{}", prefix, key);
+        match value {
+            Value::Dict(_, inner) => leaves(inner, &format!("{}.", path), paths),
+            _ => paths.push(path),
+        }
+    }
+}
+
+/// Explain the value at a key path.
+fn explain(figment: &Figment, fields: &[Field], files: &mut Files, path: String) -> Entry {
+    let (layer, origin) = locate(figment, files, &path);
+
+    // without a declared field, the value may be a secret
+    let secret = field::find(fields, &path).is_none_or(Field::is_secret)
+        || origin
+            .as_deref()
+            .is_some_and(|origin| origin.ends_with(FILE_SUFFIX));
+    let value = match figment.find_value(&path) {
+        Ok(_) if secret => REDACTED.to_string(),
+        Ok(value) => Literal(&value).to_string(),
+        Err(_) => String::new(),
+    };
+
+    Entry {
+        path,
+        value,
+        layer,
+        origin,
+    }
+}
+
+/// Find the layer that supplied the value at a key path and its origin
+/// within the layer.
+pub(crate) fn locate(
+    figment: &Figment,
+    files: &mut Files,
+    path: &str,
+) -> (Option<Layer>, Option<String>) {
+    let metadata = figment.find_metadata(path);
+    let layer = metadata.and_then(|metadata| Layer::from_name(&metadata.name));
+    let origin = metadata.and_then(|metadata| match (&metadata.source, layer) {
+        (Some(Source::File(file)), _) => Some(match files.line(file, path) {
+            Some(line) => format!("{}:{}", file.display(), line),
+            None => file.display().to_string(),
+        }),
+        (_, Some(Layer::Env)) | (_, Some(Layer::Args)) => {
+            let keys: Vec<&str> = path.split('.').collect();
+            Some(metadata.interpolate(figment.profile(), &keys))
+        }
+        (Some(Source::Custom(source)), _) => Some(source.clone()),
+        _ => None,
+    });
+    (layer, origin)
+}
+
+/// The contents of the config files, read once.
+#[derive(Default)]
+pub(crate) struct Files(HashMap<PathBuf, Option<String>>);
+
+impl Files {
+    /// Find the line of a key path in a file.
+    ///
+    /// This is a best effort: the file is scanned for the keys of the path in
+    /// order, and TOML table headers are followed.
+    fn line(&mut self, file: &Path, path: &str) -> Option<usize> {
+        let text = self
+            .0
+            .entry(file.to_path_buf())
+            .or_insert_with(|| fs::read_to_string(file).ok())
+            .as_deref()?;
+        let keys: Vec<&str> = path.split('.').collect();
+
+        let mut matched = 0;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim().trim_start_matches("- ");
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let header: Vec<&str> = header
+                    .split('.')
+                    .map(|key| key.trim().trim_matches(&['"', '\''][..]))
+                    .collect();
+                // keys under other tables cannot match until the next header
+                matched = if keys.starts_with(&header) {
+                    header.len()
+                } else {
+                    keys.len()
+                };
+                continue;
+            }
+            if matched < keys.len() && is_key(line, keys[matched]) {
+                matched += 1;
+                if matched == keys.len() {
+                    return Some(i + 1);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Returns whether `line` sets `key`, in TOML, YAML or JSON.
+fn is_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start_matches(&['"', '\''][..]);
+    match line.strip_prefix(key) {
+        Some(rest) => rest
+            .trim_start_matches(&['"', '\''][..])
+            .trim_start()
+            .starts_with(&['=', ':'][..]),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use figment::value::Value;
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use crate::AppConfig;
+    use crate::Field;
+    use crate::Layer;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {}
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("workers", "u32").with_default(Value::from(4)),
+                Field::new("database", "Database").with_fields(vec![
+                    Field::new("url", "String"),
+                    Field::new("password", "Secret<String>"),
+                    Field::new("pool", "u32"),
+                ]),
+                Field::new("token", "Option<Secret<String>>"),
+                Field::new("debug", "bool"),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.toml",
+                "debug = false\n\n[database]\n# the pool\npool = 2\nurl = 'postgres://db'",
+            )?;
+            jail.create_file("token", "abc")?;
+            jail.set_env("APP_DATABASE__PASSWORD", "hunter2");
+            jail.set_env("APP_TOKEN_FILE", "token");
+
+            let explanation = TestConfig::loader()
+                .args(vec!["--debug"])
+                .explain()
+                .unwrap();
+            let entry = |path| {
+                let entry = explanation.get(path).unwrap();
+                (entry.value(), entry.layer(), entry.origin())
+            };
+            assert_eq!(entry("workers"), ("4", Some(Layer::Defaults), None));
+            assert_eq!(entry("debug"), ("true", Some(Layer::Args), Some("--debug")));
+            assert_eq!(
+                entry("database.password"),
+                (
+                    "<redacted>",
+                    Some(Layer::Env),
+                    Some("APP_DATABASE__PASSWORD")
+                )
+            );
+            assert_eq!(
+                entry("token"),
+                ("<redacted>", Some(Layer::Env), Some("APP_TOKEN_FILE"))
+            );
+
+            let url = explanation.get("database.url").unwrap();
+            assert_eq!(url.value(), r#""postgres://db""#);
+            assert!(url.origin().unwrap().ends_with("config.toml:6"));
+            assert!(explanation
+                .get("database.pool")
+                .unwrap()
+                .origin()
+                .unwrap()
+                .ends_with("config.toml:5"));
+
+            let report = explanation.to_string();
+            assert!(report.contains("workers = 4  # defaults\n"));
+            assert!(!report.contains("hunter2"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_explain_undeclared() {
+        #[derive(Debug, Deserialize)]
+        struct UndeclaredConfig {}
+
+        impl AppConfig for UndeclaredConfig {
+            const PACKAGE: &'static str = "APP_";
+        }
+
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_PASSWORD", "hunter2");
+
+            let explanation = UndeclaredConfig::explain().unwrap();
+            let password = explanation.get("password").unwrap();
+            assert_eq!(password.value(), "<redacted>");
+            assert_eq!(password.origin(), Some("APP_PASSWORD"));
+            Ok(())
+        });
+    }
+}
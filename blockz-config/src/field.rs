@@ -0,0 +1,350 @@
+//! Descriptions of the fields of app configurations.
+
+use std::fmt;
+use std::fmt::Display;
+
+use figment::value::Dict;
+use figment::value::Empty;
+use figment::value::Value;
+
+use crate::Constraint;
+
+/// A field of an app configuration, as declared with the [`config`] macro.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Field {
+    /// The name of the field.
+    pub name: &'static str,
+    /// The type of the field, as it was declared.
+    pub ty: &'static str,
+    /// The lines of the documentation of the field.
+    pub docs: &'static [&'static str],
+    /// The default value of the field, if it has one.
+    pub default: Option<Value>,
+    /// The environment variable that sets the field, besides the one under
+    /// the package prefix.
+    pub env: Option<&'static str>,
+    /// The constraints on the value of the field.
+    pub constraints: Vec<Constraint>,
+    /// The fields of the section, if the field is a section.
+    pub fields: Vec<Field>,
+    section: bool,
+}
+
+impl Field {
+    /// Create a new `Field`.
+    pub fn new(name: &'static str, ty: &'static str) -> Self {
+        Self {
+            name,
+            ty,
+            docs: &[],
+            default: None,
+            env: None,
+            constraints: Vec::new(),
+            fields: Vec::new(),
+            section: false,
+        }
+    }
+
+    /// Set the lines of the documentation of the field.
+    pub fn with_docs(mut self, docs: &'static [&'static str]) -> Self {
+        self.docs = docs;
+        self
+    }
+
+    /// Set the default value of the field.
+    pub fn with_default(mut self, default: Value) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    /// Set the environment variable that sets the field.
+    pub fn with_env(mut self, env: &'static str) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Add a constraint on the value of the field.
+    pub fn with_constraint(mut self, constraint: Constraint) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Make the field a section with `fields`.
+    pub fn with_fields(mut self, fields: Vec<Field>) -> Self {
+        self.fields = fields;
+        self.section = true;
+        self
+    }
+
+    /// Returns whether the field is a section.
+    pub fn is_section(&self) -> bool {
+        self.section
+    }
+
+    /// Returns whether the value of the field is, or holds, a
+    /// [`Secret`](crate::Secret).
+    pub fn is_secret(&self) -> bool {
+        self.ty.contains("Secret<")
+    }
+
+    /// Returns whether the field must be set.
+    ///
+    /// A section must be set if any of its fields must be.
+    pub fn is_required(&self) -> bool {
+        if self.is_section() {
+            return self.fields.iter().any(Field::is_required);
+        }
+        self.default.is_none() && Type::parse(self.ty).name != "Option"
+    }
+
+    /// Returns whether the value of the field is a list, such as a `Vec`.
+    pub fn is_list(&self) -> bool {
+        !self.is_section() && Type::parse(self.ty).unwrapped().is_list()
+    }
+
+    /// Get the documentation of the field, with the lines trimmed.
+    pub fn doc(&self) -> String {
+        let lines: Vec<&str> = self.docs.iter().map(|line| line.trim()).collect();
+        lines.join("\n")
+    }
+}
+
+/// A struct whose fields are described, such as the sections declared with
+/// the [`config`] macro.
+pub trait Section {
+    /// Get the fields of the struct.
+    fn fields() -> Vec<Field>;
+}
+
+/// Get the default values of `fields`.
+pub(crate) fn defaults(fields: &[Field]) -> Dict {
+    let mut dict = Dict::new();
+    for field in fields {
+        if let Some(default) = &field.default {
+            dict.insert(field.name.to_string(), default.clone());
+        } else if field.is_section() {
+            let section = defaults(&field.fields);
+            if !section.is_empty() {
+                dict.insert(field.name.to_string(), section.into());
+            }
+        }
+    }
+    dict
+}
+
+/// Get the environment variables that set `fields`, with the key paths of
+/// the fields.
+pub(crate) fn env_vars(fields: &[Field]) -> Vec<(&'static str, String)> {
+    fn walk(fields: &[Field], prefix: &str, vars: &mut Vec<(&'static str, String)>) {
+        for field in fields {
+            let path = format!("{}{}", prefix, field.name);
+            if let Some(env) = field.env {
+                vars.push((env, path.clone()));
+            }
+            walk(&field.fields, &format!("{}.", path), vars);
+        }
+    }
+
+    let mut vars = Vec::new();
+    walk(fields, "", &mut vars);
+    vars
+}
+
+/// A Rust type, split into its name and its generic arguments.
+pub(crate) struct Type<'a> {
+    pub name: &'a str,
+    pub args: Vec<&'a str>,
+}
+
+impl<'a> Type<'a> {
+    /// The wrappers that are transparent to the value.
+    const WRAPPERS: &'static [&'static str] = &["Option", "Secret", "Box", "Arc"];
+
+    /// The lists.
+    const LISTS: &'static [&'static str] = &["Vec", "VecDeque", "HashSet", "BTreeSet", "[]"];
+
+    /// Parse a type as it is written, e.g. `std::vec::Vec<String>`.
+    ///
+    /// Arrays and slices are named `[]`.
+    pub(crate) fn parse(ty: &'a str) -> Self {
+        let ty = ty.trim();
+        if let Some(inner) = ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+            let item = inner.split(';').next().unwrap_or(inner);
+            return Self {
+                name: "[]",
+                args: vec![item.trim()],
+            };
+        }
+
+        let (path, args) = match ty.find('<') {
+            Some(i) if ty.ends_with('>') => (&ty[..i], split_args(&ty[i + 1..ty.len() - 1])),
+            _ => (ty, Vec::new()),
+        };
+        let name = path.rsplit("::").next().unwrap_or(path).trim();
+        Self { name, args }
+    }
+
+    /// Get the type without its transparent wrappers, such as `Option`.
+    pub(crate) fn unwrapped(self) -> Self {
+        match self.args.as_slice() {
+            [inner] if Self::WRAPPERS.contains(&self.name) => Self::parse(inner).unwrapped(),
+            _ => self,
+        }
+    }
+
+    /// Returns whether the type is a list.
+    pub(crate) fn is_list(&self) -> bool {
+        Self::LISTS.contains(&self.name)
+    }
+}
+
+/// Split generic arguments on the commas that are not nested.
+fn split_args(args: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' | '[' | '(' => depth += 1,
+            '>' | ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(args[start..].trim());
+    split
+}
+
+/// Find the field at a key path.
+pub(crate) fn find<'a>(fields: &'a [Field], path: &str) -> Option<&'a Field> {
+    let (name, rest) = match path.split_once('.') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (path, None),
+    };
+    let field = fields.iter().find(|field| field.name == name)?;
+    match rest {
+        Some(rest) => find(&field.fields, rest),
+        None => Some(field),
+    }
+}
+
+/// Insert a value at a key path into `dict`, creating the dicts on the way.
+pub(crate) fn insert(dict: &mut Dict, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((key, rest)) => {
+            let entry = dict
+                .entry(key.to_string())
+                .or_insert_with(|| Dict::new().into());
+            if entry.as_dict().is_none() {
+                *entry = Dict::new().into();
+            }
+            if let Value::Dict(_, inner) = entry {
+                insert(inner, rest, value);
+            }
+        }
+        None => {
+            dict.insert(path.to_string(), value);
+        }
+    }
+}
+
+/// Formats a value as an inline TOML value.
+pub(crate) struct Literal<'a>(pub &'a Value);
+
+impl Display for Literal<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::String(_, s) => write!(f, "{:?}", s),
+            Value::Char(_, c) => write!(f, "{:?}", c.to_string()),
+            Value::Bool(_, b) => write!(f, "{}", b),
+            Value::Num(_, n) => match (n.to_u128(), n.to_i128(), n.to_f64()) {
+                (Some(n), _, _) => write!(f, "{}", n),
+                (_, Some(n), _) => write!(f, "{}", n),
+                (_, _, Some(n)) => write!(f, "{:?}", n),
+                _ => Ok(()),
+            },
+            Value::Empty(_, Empty::None) | Value::Empty(_, Empty::Unit) => Ok(()),
+            Value::Array(_, items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", Literal(item))?;
+                }
+                f.write_str("]")
+            }
+            Value::Dict(_, dict) => {
+                f.write_str("{ ")?;
+                for (i, (key, value)) in dict.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{} = {}", key, Literal(value))?;
+                }
+                f.write_str(" }")
+            }
+        }
+    }
+}
+
+/// Get the key paths of `fields` that are not sections.
+pub(crate) fn paths(fields: &[Field]) -> Vec<String> {
+    fn walk(fields: &[Field], prefix: &str, paths: &mut Vec<String>) {
+        for field in fields {
+            let path = format!("{}{}", prefix, field.name);
+            if field.is_section() {
+                walk(&field.fields, &format!("{}.", path), paths);
+            } else {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut paths = Vec::new();
+    walk(fields, "", &mut paths);
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use figment::value::Value;
+
+    use super::defaults;
+    use super::env_vars;
+    use super::paths;
+    use super::Field;
+
+    #[test]
+    fn test_field_defaults_nested() {
+        let fields = vec![
+            Field::new("port", "u16").with_default(Value::from(80)),
+            Field::new("host", "String"),
+            Field::new("database", "Database").with_fields(vec![
+                Field::new("url", "String").with_env("DATABASE_URL"),
+                Field::new("pool", "u32").with_default(Value::from(4)),
+            ]),
+        ];
+
+        let dict = defaults(&fields);
+        assert_eq!(dict.len(), 2);
+        assert_eq!(dict["port"], Value::from(80));
+        assert_eq!(
+            dict["database"].find_ref("pool").cloned(),
+            Some(Value::from(4))
+        );
+
+        assert_eq!(
+            env_vars(&fields),
+            vec![("DATABASE_URL", "database.url".to_string())]
+        );
+        assert_eq!(
+            paths(&fields),
+            vec!["port", "host", "database.url", "database.pool"]
+        );
+    }
+}
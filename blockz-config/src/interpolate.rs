@@ -0,0 +1,452 @@
+//! References to other values and to environment variables inside the values
+//! of app configurations.
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+
+use figment::value::Dict;
+use figment::value::Map;
+use figment::value::Tag;
+use figment::value::Value;
+use figment::Figment;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+
+use crate::field;
+use crate::field::Literal;
+use crate::Field;
+use crate::Layer;
+
+/// An error in the references inside the values of a configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InterpolationError {
+    /// A reference is neither a key nor a set environment variable, and has
+    /// no default.
+    Unresolved {
+        /// The key path of the value that holds the reference.
+        path: String,
+        /// The reference, e.g. `REDIS_HOST` for `${REDIS_HOST}`.
+        reference: String,
+    },
+    /// A value references itself, through the other keys of the cycle.
+    Cycle {
+        /// The key paths of the cycle, starting and ending with the same key.
+        cycle: Vec<String>,
+    },
+    /// A reference is not closed with `}`.
+    Unterminated {
+        /// The key path of the value that holds the reference.
+        path: String,
+    },
+}
+
+impl Display for InterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unresolved { path, reference } => write!(
+                f,
+                "`{}` references `${{{}}}`, which is neither a key nor a set environment variable",
+                path, reference
+            ),
+            Self::Cycle { cycle } => {
+                let cycle: Vec<String> = cycle.iter().map(|path| format!("`{}`", path)).collect();
+                write!(f, "cyclic references: {}", cycle.join(" -> "))
+            }
+            Self::Unterminated { path } => write!(f, "`{}` has an unterminated `${{`", path),
+        }
+    }
+}
+
+impl Error for InterpolationError {}
+
+/// Resolve the references inside the string values of `figment` that come
+/// from the config file and the directory trees, except for secrets.
+///
+/// A `${name}` reference is replaced with the value of the `name` key path,
+/// e.g. `${cache.port}`, or else with the `name` environment variable, e.g.
+/// `${REDIS_HOST}`. `${name:-default}` falls back to `default` when neither
+/// is set, or the variable is empty. A value that is a single reference takes
+/// the type of the referenced value. `$$` is a literal `$`.
+///
+/// The values of the other layers, such as environment variables, may hold
+/// any `$` and are taken as is, including when they are referenced. The
+/// resolved values keep the layers of the values that held the references.
+pub(crate) fn interpolate(
+    figment: Figment,
+    fields: &[Field],
+) -> Result<Figment, InterpolationError> {
+    // errors in the layers are reported when the configuration is extracted
+    let root = match figment.find_value("") {
+        Ok(root) => root,
+        Err(_) => return Ok(figment),
+    };
+
+    let mut paths = Vec::new();
+    leaves(&root, "", &mut paths);
+    paths.retain(|path| {
+        let layer = figment
+            .find_metadata(path)
+            .and_then(|metadata| Layer::from_name(&metadata.name));
+        layer == Some(Layer::File) && !field::find(fields, path).is_some_and(Field::is_secret)
+    });
+    let mut resolver = Resolver {
+        root: &root,
+        paths: &paths,
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+    };
+    let mut changed = Dict::new();
+    for path in &paths {
+        if let Some(value) = resolver.key(path)? {
+            field::insert(&mut changed, path, value);
+        }
+    }
+
+    if changed.is_empty() {
+        Ok(figment)
+    } else {
+        Ok(figment.merge(Interpolated(changed)))
+    }
+}
+
+/// Get the key paths of the values in `value` that hold references.
+fn leaves(value: &Value, prefix: &str, paths: &mut Vec<String>) {
+    match value {
+        Value::Dict(_, dict) => {
+            for (key, value) in dict {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                leaves(value, &path, paths);
+            }
+        }
+        value if has_references(value) => paths.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+/// Returns whether a value holds references or escapes.
+fn has_references(value: &Value) -> bool {
+    match value {
+        Value::String(_, s) => s.contains('$'),
+        Value::Array(_, items) => items.iter().any(has_references),
+        _ => false,
+    }
+}
+
+/// A part of a string value.
+enum Part<'a> {
+    Text(String),
+    Reference(&'a str),
+}
+
+/// Split a string into text and references.
+fn parse<'a>(s: &'a str, path: &str) -> Result<Vec<Part<'a>>, InterpolationError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            text.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| InterpolationError::Unterminated {
+                    path: path.to_string(),
+                })?;
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(Part::Reference(&after[..end]));
+            rest = &after[end + 1..];
+        } else {
+            text.push('$');
+            rest = &rest[1..];
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Resolves the references of the values in a dict, once each.
+struct Resolver<'a> {
+    root: &'a Value,
+    /// The key paths of the values whose references are resolved.
+    paths: &'a [String],
+    resolved: HashMap<String, Value>,
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// Get the value of a key path with its references resolved, if it has
+    /// any.
+    fn key(&mut self, path: &str) -> Result<Option<Value>, InterpolationError> {
+        let value = match self.root.find_ref(path) {
+            Some(value) if self.paths.iter().any(|other| other == path) => value,
+            _ => return Ok(None),
+        };
+        if let Some(resolved) = self.resolved.get(path) {
+            return Ok(Some(resolved.clone()));
+        }
+        if let Some(start) = self.stack.iter().position(|other| other == path) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(path.to_string());
+            return Err(InterpolationError::Cycle { cycle });
+        }
+
+        self.stack.push(path.to_string());
+        let resolved = self.value(path, value)?;
+        self.stack.pop();
+        self.resolved.insert(path.to_string(), resolved.clone());
+        Ok(Some(resolved))
+    }
+
+    /// Resolve the references in `value`, which is at a key path.
+    fn value(&mut self, path: &str, value: &Value) -> Result<Value, InterpolationError> {
+        match value {
+            Value::String(tag, s) if s.contains('$') => {
+                let resolved = match parse(s, path)?.as_slice() {
+                    [Part::Reference(reference)] => self.reference(path, reference)?,
+                    parts => {
+                        let mut joined = String::new();
+                        for part in parts {
+                            match part {
+                                Part::Text(text) => joined.push_str(text),
+                                Part::Reference(reference) => {
+                                    match self.reference(path, reference)? {
+                                        Value::String(_, s) => joined.push_str(&s),
+                                        value => joined.push_str(&Literal(&value).to_string()),
+                                    }
+                                }
+                            }
+                        }
+                        Value::from(joined)
+                    }
+                };
+                Ok(retag(resolved, *tag))
+            }
+            Value::Array(tag, items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.value(path, item))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::Array(*tag, items))
+            }
+            value => Ok(value.clone()),
+        }
+    }
+
+    /// Resolve a reference in the value at a key path.
+    fn reference(&mut self, path: &str, reference: &str) -> Result<Value, InterpolationError> {
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (reference.trim(), None),
+        };
+
+        if let Some(value) = self.key(name)? {
+            return Ok(value);
+        }
+        if let Some(value) = self.root.find_ref(name) {
+            if value.as_dict().is_none() {
+                return Ok(value.clone());
+            }
+        }
+        match (env::var(name), default) {
+            (Ok(value), None) => Ok(parse_value(&value)),
+            (Ok(value), Some(_)) if !value.is_empty() => Ok(parse_value(&value)),
+            (_, Some(default)) => Ok(parse_value(default)),
+            (Err(_), None) => Err(InterpolationError::Unresolved {
+                path: path.to_string(),
+                reference: reference.to_string(),
+            }),
+        }
+    }
+}
+
+/// Parse a value from an environment variable or a default.
+fn parse_value(s: &str) -> Value {
+    s.parse().expect("parsing a value is infallible")
+}
+
+/// Give a value the tag of the value it replaces.
+fn retag(value: Value, tag: Tag) -> Value {
+    match value {
+        Value::String(_, v) => Value::String(tag, v),
+        Value::Char(_, v) => Value::Char(tag, v),
+        Value::Bool(_, v) => Value::Bool(tag, v),
+        Value::Num(_, v) => Value::Num(tag, v),
+        Value::Empty(_, v) => Value::Empty(tag, v),
+        Value::Dict(_, v) => Value::Dict(tag, v),
+        Value::Array(_, v) => Value::Array(tag, v),
+    }
+}
+
+/// A provider for the values whose references were resolved.
+struct Interpolated(Dict);
+
+impl Provider for Interpolated {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("interpolation")
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        Ok(Profile::Global.collect(self.0.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::InterpolationError;
+    use crate::AppConfig;
+    use crate::Field;
+    use crate::Layer;
+    use crate::Secret;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        url: String,
+        port: u16,
+        note: String,
+        hosts: Vec<String>,
+        cache: Cache,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Cache {
+        port: u16,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+    }
+
+    #[test]
+    fn test_interpolate() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.toml",
+                r#"
+                url = "redis://${REDIS_HOST}:${cache.port}/0"
+                port = "${cache.port}"
+                note = "costs $$5, ${MISSING:-nothing} or $"
+                hosts = ["${REDIS_HOST}", "backup"]
+
+                [cache]
+                port = "${CACHE_PORT:-6379}"
+                "#,
+            )?;
+            jail.set_env("REDIS_HOST", "redis");
+
+            let config = TestConfig::load().unwrap();
+            assert_eq!(config.url, "redis://redis:6379/0");
+            assert_eq!(config.port, 6379);
+            assert_eq!(config.cache.port, 6379);
+            assert_eq!(config.note, "costs $5, nothing or $");
+            assert_eq!(config.hosts, ["redis", "backup"]);
+
+            jail.set_env("APP_CACHE__PORT", 6380);
+            let config = TestConfig::load().unwrap();
+            assert_eq!(config.url, "redis://redis:6380/0");
+
+            let explanation = TestConfig::explain().unwrap();
+            assert_eq!(explanation.get("url").unwrap().layer(), Some(Layer::File));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.toml",
+                "url = '${REDIS_HOST}'\nport = 1\nnote = ''\nhosts = []\ncache = { port = 1 }",
+            )?;
+            let err = TestConfig::load().unwrap_err();
+            assert_eq!(
+                err.interpolation(),
+                Some(&InterpolationError::Unresolved {
+                    path: "url".to_string(),
+                    reference: "REDIS_HOST".to_string(),
+                })
+            );
+            assert!(err.to_string().contains("`${REDIS_HOST}`"));
+
+            jail.create_file(
+                "config.toml",
+                "url = '${note}'\nport = 1\nnote = '${url}'\nhosts = []\ncache = { port = 1 }",
+            )?;
+            let err = TestConfig::load().unwrap_err();
+            assert_eq!(
+                err.interpolation(),
+                Some(&InterpolationError::Cycle {
+                    cycle: vec!["note".to_string(), "url".to_string(), "note".to_string()],
+                })
+            );
+
+            jail.create_file(
+                "config.toml",
+                "url = '${note'\nport = 1\nnote = ''\nhosts = []\ncache = { port = 1 }",
+            )?;
+            let err = TestConfig::load().unwrap_err();
+            assert_eq!(
+                err.interpolation(),
+                Some(&InterpolationError::Unterminated {
+                    path: "url".to_string(),
+                })
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_interpolate_files_only() {
+        #[derive(Debug, Deserialize)]
+        struct SecretConfig {
+            password: Secret<String>,
+            note: String,
+            url: String,
+        }
+
+        impl AppConfig for SecretConfig {
+            const PACKAGE: &'static str = "APP_";
+
+            fn fields() -> Vec<Field> {
+                vec![
+                    Field::new("password", "Secret<String>"),
+                    Field::new("note", "String"),
+                    Field::new("url", "String"),
+                ]
+            }
+        }
+
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.toml",
+                "password = 'ab$$cd${x'\nnote = ''\nurl = '${note}'",
+            )?;
+            jail.set_env("APP_NOTE", "pa$${x");
+
+            let config = SecretConfig::load().unwrap();
+            assert_eq!(config.password.expose(), "ab$$cd${x");
+            assert_eq!(config.note, "pa$${x");
+            assert_eq!(config.url, "pa$${x");
+            Ok(())
+        });
+    }
+}
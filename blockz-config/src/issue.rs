@@ -0,0 +1,662 @@
+//! Keys of app configurations that are missing, hold values of the wrong
+//! type or do not map to any field.
+
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fmt::Display;
+
+use figment::error::Kind;
+use figment::value::Num;
+use figment::value::Value;
+use figment::Figment;
+
+use crate::env::env_prefix;
+use crate::env::env_var;
+use crate::env::key_path;
+use crate::explain;
+use crate::explain::Files;
+use crate::explain::REDACTED;
+use crate::field;
+use crate::field::Literal;
+use crate::field::Type;
+use crate::secret::FILE_SUFFIX;
+use crate::suggest;
+use crate::AppConfig;
+use crate::Field;
+use crate::Layer;
+use crate::CONFIG_FILE_KEY;
+use crate::PROFILE_KEY;
+
+/// How the keys that do not map to any field are handled, see
+/// [`AppConfig::STRICT`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strict {
+    /// Unknown keys are ignored.
+    Off,
+    /// Unknown keys are reported to [`AppConfig::warn`] when the
+    /// configuration is loaded, or returned by
+    /// [`AppConfig::load_with_warnings`] and
+    /// [`Loader::load_with_warnings`](crate::Loader::load_with_warnings).
+    Warn,
+    /// Unknown keys fail loading.
+    Deny,
+}
+
+/// What is wrong with a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IssueKind {
+    /// The key is not set by any layer.
+    Missing,
+    /// The value of the key cannot be parsed as its type.
+    Invalid,
+    /// The key does not map to any field, e.g. because it is misspelled.
+    Unknown,
+}
+
+/// A key that is missing, holds a value of the wrong type or does not map
+/// to any field.
+#[derive(Clone, Debug)]
+pub struct Issue {
+    kind: IssueKind,
+    path: String,
+    expected: Option<String>,
+    found: Option<String>,
+    layer: Option<Layer>,
+    origin: Option<String>,
+    env: String,
+    misspelled: Option<String>,
+    suggestion: Option<String>,
+}
+
+impl Issue {
+    /// Get what is wrong with the key.
+    pub fn kind(&self) -> IssueKind {
+        self.kind
+    }
+
+    /// Get the key path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the expected type of the value, if it is known.
+    pub fn expected(&self) -> Option<&str> {
+        self.expected.as_deref()
+    }
+
+    /// Get the invalid value, as an inline TOML value, or `<redacted>` for
+    /// secrets and for keys without a declared field.
+    pub fn found(&self) -> Option<&str> {
+        self.found.as_deref()
+    }
+
+    /// Get the layer that supplied the invalid or unknown value, if it is
+    /// known.
+    pub fn layer(&self) -> Option<Layer> {
+        self.layer
+    }
+
+    /// Get the origin of the invalid or unknown value within its layer, if it
+    /// is known.
+    ///
+    /// This is the path of the config file with the line of the key, the
+    /// environment variable or the command-line flag.
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    /// Get the environment variable that sets the key.
+    pub fn env(&self) -> &str {
+        &self.env
+    }
+
+    /// Get the environment variable under the prefix that is set and is
+    /// likely a misspelling of [`env`](Issue::env), if there is one.
+    pub fn misspelled(&self) -> Option<&str> {
+        self.misspelled.as_deref()
+    }
+
+    /// Get the valid key that is closest to an unknown key, if one is close
+    /// enough to be what was meant.
+    ///
+    /// This is the environment variable of the valid key if the unknown key
+    /// was set by one, or else its key path.
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_deref()
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind, &self.found) {
+            (IssueKind::Invalid, Some(found)) => {
+                write!(f, "invalid value {} for `{}`", found, self.path)?
+            }
+            (IssueKind::Invalid, None) => write!(f, "invalid value for `{}`", self.path)?,
+            (IssueKind::Missing, _) => write!(f, "missing field `{}`", self.path)?,
+            (IssueKind::Unknown, _) => write!(f, "unknown key `{}`", self.path)?,
+        }
+        if let Some(expected) = &self.expected {
+            write!(f, " of type `{}`", expected)?;
+        }
+
+        match (self.kind, self.layer, &self.origin) {
+            (IssueKind::Missing, ..) => write!(f, ", set it with `{}`", self.env)?,
+            (_, Some(Layer::File), Some(origin)) => {
+                write!(f, " (set by the config file at `{}`)", origin)?
+            }
+            (_, _, Some(origin)) => write!(f, " (set by `{}`)", origin)?,
+            (_, Some(layer), None) => write!(f, " (set by the {})", layer)?,
+            (_, None, None) => {}
+        }
+        if let Some(misspelled) = &self.misspelled {
+            write!(f, " (found `{}`, did you mean `{}`?)", misspelled, self.env)?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean `{}`?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find every key that is missing or holds a value of the wrong type.
+///
+/// The keys are found from the [fields](AppConfig::fields) of `T` and from
+/// `error`, the error of extracting `T` from `figment`. This is empty if
+/// `error` is not about keys, such as a config file that cannot be parsed.
+pub(crate) fn find<T: AppConfig>(figment: &Figment, error: &figment::Error) -> Vec<Issue> {
+    fn walk(
+        figment: &Figment,
+        fields: &[Field],
+        prefix: &str,
+        found: &mut Vec<(String, IssueKind)>,
+    ) {
+        for field in fields {
+            let path = format!("{}{}", prefix, field.name);
+            if field.is_section() {
+                walk(figment, &field.fields, &format!("{}.", path), found);
+                continue;
+            }
+            match figment.find_value(&path) {
+                Err(_) if field.is_required() => found.push((path, IssueKind::Missing)),
+                Ok(value) if !accepts(Type::parse(field.ty), &value) => {
+                    found.push((path, IssueKind::Invalid))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut keys = Vec::new();
+    let mut expected = Vec::new();
+    for error in error.clone() {
+        let mut path = error.path.clone();
+        let kind = match &error.kind {
+            Kind::MissingField(name) => {
+                path.push(name.to_string());
+                IssueKind::Missing
+            }
+            _ if path.is_empty() => return Vec::new(),
+            Kind::InvalidType(_, ty) | Kind::InvalidValue(_, ty) => {
+                expected.push((path.join("."), ty.clone()));
+                IssueKind::Invalid
+            }
+            _ => IssueKind::Invalid,
+        };
+        keys.push((path.join("."), kind));
+    }
+
+    let fields = T::fields();
+    let mut found = Vec::new();
+    walk(figment, &fields, "", &mut found);
+    for (path, kind) in keys {
+        let covered = found
+            .iter()
+            .any(|(other, _)| nests(&path, other) || nests(other, &path));
+        if !covered {
+            found.push((path, kind));
+        }
+    }
+
+    let unknown = unknown_vars::<T>(&fields);
+    let mut files = Files::default();
+    found
+        .into_iter()
+        .map(|(path, kind)| {
+            let field = field::find(&fields, &path);
+            let expected = match field {
+                Some(field) => Some(field.ty.to_string()),
+                None => expected
+                    .iter()
+                    .find(|(other, _)| *other == path)
+                    .map(|(_, ty)| ty.clone()),
+            };
+            let env = env_var::<T>(&path);
+            let mut issue = Issue {
+                kind,
+                path,
+                expected,
+                found: None,
+                layer: None,
+                origin: None,
+                misspelled: None,
+                suggestion: None,
+                env,
+            };
+
+            match kind {
+                IssueKind::Missing => {
+                    issue.misspelled =
+                        suggest::closest(&issue.env, unknown.iter().map(String::as_str))
+                            .map(String::from);
+                }
+                IssueKind::Invalid | IssueKind::Unknown => {
+                    let (layer, origin) = explain::locate(figment, &mut files, &issue.path);
+                    issue.layer = layer;
+                    issue.origin = origin;
+                    issue.found = figment
+                        .find_value(&issue.path)
+                        .ok()
+                        .map(|value| match field {
+                            Some(field) if !field.is_secret() => Literal(&value).to_string(),
+                            _ => REDACTED.to_string(),
+                        });
+                }
+            }
+            issue
+        })
+        .collect()
+}
+
+/// Find every key of `figment` that does not map to a field of `T`.
+///
+/// A key under a field that is not a section, such as a map, is known. This
+/// is empty if `T` does not describe its [fields](AppConfig::fields).
+pub(crate) fn unknown<T: AppConfig>(figment: &Figment) -> Vec<Issue> {
+    fn walk(value: &Value, prefix: &str, paths: &[String], found: &mut Vec<String>) {
+        let dict = match value.as_dict() {
+            Some(dict) => dict,
+            None => return,
+        };
+        for (key, value) in dict {
+            let path = format!("{}{}", prefix, key);
+            if paths.iter().any(|known| nests(&path, known)) {
+                continue;
+            }
+            if paths.iter().any(|known| nests(known, &path)) {
+                walk(value, &format!("{}.", path), paths, found);
+            } else {
+                found.push(path);
+            }
+        }
+    }
+
+    let fields = T::fields();
+    let root = match figment.find_value("") {
+        Ok(root) if !fields.is_empty() => root,
+        _ => return Vec::new(),
+    };
+    let paths = field::paths(&fields);
+    let mut found = Vec::new();
+    walk(&root, "", &paths, &mut found);
+
+    // sections are candidates too, for misspelled tables
+    let mut candidates: Vec<&str> = Vec::new();
+    for path in &paths {
+        for (i, c) in path.char_indices() {
+            if c == '.' && !candidates.contains(&&path[..i]) {
+                candidates.push(&path[..i]);
+            }
+        }
+        candidates.push(path);
+    }
+
+    let mut files = Files::default();
+    found
+        .into_iter()
+        .map(|path| {
+            let (layer, origin) = explain::locate(figment, &mut files, &path);
+            let suggestion =
+                suggest::closest(&path, candidates.iter().copied()).map(|known| match layer {
+                    Some(Layer::Env) => env_var::<T>(known),
+                    _ => known.to_string(),
+                });
+            Issue {
+                kind: IssueKind::Unknown,
+                expected: None,
+                found: None,
+                layer,
+                origin,
+                env: env_var::<T>(&path),
+                misspelled: None,
+                suggestion,
+                path,
+            }
+        })
+        .collect()
+}
+
+/// Returns whether the key path `inner` is `outer` or nested in it.
+fn nests(inner: &str, outer: &str) -> bool {
+    inner
+        .strip_prefix(outer)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Returns whether `value` can be parsed as `ty`.
+///
+/// This is a best effort: only the booleans, numbers and lists of them are
+/// checked, and every value is accepted for other types.
+fn accepts(ty: Type<'_>, value: &Value) -> bool {
+    let ty = ty.unwrapped();
+    if ty.is_list() {
+        return match (value, ty.args.as_slice()) {
+            (Value::Array(_, items), [item]) => {
+                items.iter().all(|value| accepts(Type::parse(item), value))
+            }
+            (Value::Array(..), _) => true,
+            _ => false,
+        };
+    }
+
+    let num = match value {
+        Value::Num(_, num) => Some(*num),
+        _ => None,
+    };
+    let (min, max) = match ty.name {
+        "bool" => return matches!(value, Value::Bool(..)),
+        "f32" | "f64" => return num.is_some(),
+        "u8" => (0, u8::MAX.into()),
+        "u16" => (0, u16::MAX.into()),
+        "u32" => (0, u32::MAX.into()),
+        "u64" | "usize" => (0, u64::MAX.into()),
+        "i8" => (i8::MIN.into(), i8::MAX.into()),
+        "i16" => (i16::MIN.into(), i16::MAX.into()),
+        "i32" => (i32::MIN.into(), i32::MAX.into()),
+        "i64" | "isize" => (i64::MIN.into(), i64::MAX.into()),
+        _ => return true,
+    };
+    num.and_then(to_i128)
+        .is_some_and(|n: i128| (min..=max).contains(&n))
+}
+
+/// Convert an integer to an `i128`.
+fn to_i128(num: Num) -> Option<i128> {
+    num.to_i128()
+        .or_else(|| num.to_u128().and_then(|n| i128::try_from(n).ok()))
+}
+
+/// Get the environment variables under the prefix of `T` that do not set any
+/// of `fields`.
+fn unknown_vars<T: AppConfig>(fields: &[Field]) -> Vec<String> {
+    if fields.is_empty() || env_prefix::<T>().is_empty() {
+        return Vec::new();
+    }
+
+    let paths = field::paths(fields);
+    let known = |path: &str| {
+        path == CONFIG_FILE_KEY
+            || path == PROFILE_KEY
+            || paths.iter().any(|known| nests(path, known))
+    };
+    env::vars_os()
+        .filter_map(|(var, _)| var.into_string().ok())
+        .filter(|var| match key_path::<T>(var) {
+            Some(path) => {
+                let suffix = FILE_SUFFIX.to_ascii_lowercase();
+                let direct = path.strip_suffix(&suffix).unwrap_or(&path);
+                !known(&path) && !known(direct)
+            }
+            None => false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use figment::value::Dict;
+    use figment::value::Value;
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::Issue;
+    use super::IssueKind;
+    use super::Strict;
+    use crate::AppConfig;
+    use crate::Field;
+    use crate::Layer;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct TestConfig {
+        port: u16,
+        debug: bool,
+        peers: Vec<u16>,
+        database: Database,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Database {
+        url: String,
+        password: String,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("port", "u16").with_default(Value::from(8080)),
+                Field::new("debug", "bool").with_default(Value::from(false)),
+                Field::new("peers", "Vec<u16>").with_default(Value::from(Vec::<Value>::new())),
+                Field::new("database", "Database").with_fields(vec![
+                    Field::new("url", "String"),
+                    Field::new("password", "Secret<String>"),
+                ]),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_issues() {
+        Jail::expect_with(|jail| {
+            jail.create_file("config.toml", "debug = 'yes'\npeers = [1, 'two']")?;
+            jail.set_env("APP_PORT", 65536);
+            jail.set_env("APP_DATABSE_URL", "postgres://db");
+
+            let err = TestConfig::load().unwrap_err();
+            let issues: Vec<_> = err
+                .issues()
+                .iter()
+                .map(|issue| (issue.kind(), issue.path(), issue.expected(), issue.layer()))
+                .collect();
+            assert_eq!(
+                issues,
+                [
+                    (IssueKind::Invalid, "port", Some("u16"), Some(Layer::Env)),
+                    (IssueKind::Invalid, "debug", Some("bool"), Some(Layer::File)),
+                    (
+                        IssueKind::Invalid,
+                        "peers",
+                        Some("Vec<u16>"),
+                        Some(Layer::File)
+                    ),
+                    (IssueKind::Missing, "database.url", Some("String"), None),
+                    (
+                        IssueKind::Missing,
+                        "database.password",
+                        Some("Secret<String>"),
+                        None
+                    ),
+                ]
+            );
+
+            let url = &err.issues()[3];
+            assert_eq!(url.env(), "APP_DATABASE__URL");
+            assert_eq!(url.misspelled(), Some("APP_DATABSE_URL"));
+            assert_eq!(err.issues()[4].misspelled(), None);
+
+            let message = err.to_string();
+            assert!(message.contains("5 missing or invalid key(s)"));
+            assert!(message
+                .contains("invalid value 65536 for `port` of type `u16` (set by `APP_PORT`)"));
+            assert!(message.contains(
+                "invalid value \"yes\" for `debug` of type `bool` (set by the config file at `"
+            ));
+            assert!(message.contains(
+                "missing field `database.url` of type `String`, set it with `APP_DATABASE__URL` \
+                 (found `APP_DATABSE_URL`, did you mean `APP_DATABASE__URL`?)"
+            ));
+            Ok(())
+        });
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct StrictConfig {
+        log_level: String,
+        database: StrictDatabase,
+        labels: HashMap<String, String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct StrictDatabase {
+        url: String,
+    }
+
+    impl AppConfig for StrictConfig {
+        const PACKAGE: &'static str = "APP_";
+        const STRICT: Strict = Strict::Deny;
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("log_level", "String").with_default(Value::from("info")),
+                Field::new("database", "StrictDatabase")
+                    .with_fields(vec![Field::new("url", "String")]),
+                Field::new("labels", "HashMap<String, String>")
+                    .with_default(Value::from(Dict::new())),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_unknown() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.toml",
+                "[database]\nurl = 'postgres://db'\n[labels]\nteam = 'core'",
+            )?;
+            StrictConfig::load().unwrap();
+
+            jail.set_env("APP_LOG_LEVLE", "debug");
+            jail.create_file(
+                "config.toml",
+                "[database]\nurl = 'postgres://db'\nurll = 'x'\n[databse]\nurl = 'y'",
+            )?;
+            let err = StrictConfig::load().unwrap_err();
+            let issues: Vec<_> = err
+                .issues()
+                .iter()
+                .map(|issue| {
+                    (
+                        issue.kind(),
+                        issue.path(),
+                        issue.layer(),
+                        issue.suggestion(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                issues,
+                [
+                    (
+                        IssueKind::Unknown,
+                        "database.urll",
+                        Some(Layer::File),
+                        Some("database.url")
+                    ),
+                    (
+                        IssueKind::Unknown,
+                        "databse",
+                        Some(Layer::File),
+                        Some("database")
+                    ),
+                    (
+                        IssueKind::Unknown,
+                        "log_levle",
+                        Some(Layer::Env),
+                        Some("APP_LOG_LEVEL")
+                    ),
+                ]
+            );
+
+            let message = err.to_string();
+            assert!(message.contains("3 unknown key(s)"));
+            assert!(message.contains(
+                "unknown key `log_levle` (set by `APP_LOG_LEVLE`), did you mean `APP_LOG_LEVEL`?"
+            ));
+            assert!(message.contains("unknown key `database.urll` (set by the config file at `"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_unknown_warn() {
+        static WARNINGS: Mutex<Vec<Issue>> = Mutex::new(Vec::new());
+
+        #[derive(Debug, Deserialize)]
+        struct WarnConfig {
+            log_level: String,
+        }
+
+        impl AppConfig for WarnConfig {
+            const PACKAGE: &'static str = "APP_";
+            const STRICT: Strict = Strict::Warn;
+
+            fn fields() -> Vec<Field> {
+                vec![Field::new("log_level", "String")]
+            }
+
+            fn warn(issue: &Issue) {
+                WARNINGS.lock().unwrap().push(issue.clone());
+            }
+        }
+
+        let summary = |warnings: &[Issue]| -> Vec<(IssueKind, String, Option<String>)> {
+            warnings
+                .iter()
+                .map(|issue| {
+                    let suggestion = issue.suggestion().map(str::to_string);
+                    (issue.kind(), issue.path().to_string(), suggestion)
+                })
+                .collect()
+        };
+        let expected = [(
+            IssueKind::Unknown,
+            "log_levle".to_string(),
+            Some("APP_LOG_LEVEL".to_string()),
+        )];
+
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_LOG_LEVEL", "info");
+            jail.set_env("APP_LOG_LEVLE", "debug");
+
+            assert_eq!(WarnConfig::load().unwrap().log_level, "info");
+            assert_eq!(summary(&WARNINGS.lock().unwrap()), expected);
+
+            WARNINGS.lock().unwrap().clear();
+            let (config, warnings) = WarnConfig::load_with_warnings().unwrap();
+            assert_eq!(config.log_level, "info");
+            assert_eq!(summary(&warnings), expected);
+            assert!(WARNINGS.lock().unwrap().is_empty());
+            Ok(())
+        });
+    }
+}
@@ -1,82 +1,533 @@
 //! Convenient application configuration.
+//!
+//! With the `reload` feature, configurations can be reloaded when their
+//! sources change, see [`reload`]. With the `remote` feature, values can be
+//! fetched from remote services, see [`remote`].
+//!
+//! A JSON Schema, a `.env.example` file and a sample config file can be
+//! generated from a configuration, see [`template`].
+
+// `Jail` closures in tests return `figment::Error`
+#![cfg_attr(test, allow(clippy::result_large_err))]
 
-use std::error::Error;
-use std::fmt;
 use std::fmt::Debug;
-use std::fmt::Display;
 
+use figment::value::Dict;
 use serde::de::DeserializeOwned;
 
+mod cli;
+mod dotenv;
+mod env;
+mod error;
+mod explain;
+mod field;
+mod interpolate;
+mod issue;
+mod loader;
+#[cfg(feature = "reload")]
+pub mod reload;
+#[cfg(feature = "remote")]
+pub mod remote;
+mod secret;
+mod suggest;
+pub mod template;
+mod tree;
+mod validate;
+
+pub use self::cli::help;
+pub use self::cli::ArgsError;
+pub use self::env::env_prefix;
+pub use self::env::env_var;
+pub use self::env::key_path;
+pub use self::env::normalize_prefix;
+pub use self::error::*;
+pub use self::explain::Entry;
+pub use self::explain::Explanation;
+pub use self::field::Field;
+pub use self::field::Section;
+pub use self::interpolate::InterpolationError;
+pub use self::issue::Issue;
+pub use self::issue::IssueKind;
+pub use self::issue::Strict;
+pub use self::loader::*;
+pub use self::secret::Secret;
+pub use self::secret::FILE_SUFFIX;
+pub use self::tree::DirectoryTree;
+pub use self::validate::Constraint;
+pub use self::validate::Violation;
+
 /// Result type for app configurations.
-pub type Result<T> = std::result::Result<T, AppConfigError<T>>;
+pub type Result<T, C = T> = std::result::Result<T, AppConfigError<C>>;
 
 /// An application configuration.
+///
+/// The configuration is loaded from a stack of layers, each overriding the
+/// values of the layers before it:
+///
+/// 1. the compiled-in [defaults](AppConfig::defaults)
+/// 2. the optional config file, in TOML, YAML or JSON, whose path is taken
+///    from the `<PREFIX>CONFIG` environment variable or is the first of
+///    `config.toml`, `config.yaml`, `config.yml` and `config.json` in the
+///    working directory, and the [directory trees](Loader::directory) with a
+///    file per key, such as mounted Kubernetes ConfigMaps
+/// 3. the remotes given to the [`Loader`], with the `remote` feature
+/// 4. the environment variables under the [prefix](env_prefix), with nested
+///    keys joined by [`ENV_SEPARATOR`](AppConfig::ENV_SEPARATOR) and lists
+///    given as comma-separated or indexed variables, see [`env_var`];
+///    a `<VAR>_FILE` variable names a file that holds the value of `<VAR>`,
+///    for secrets mounted as files, unless `<VAR>_FILE` is itself the
+///    variable of a declared [field](AppConfig::fields); below them, the
+///    variables of the [env files](Loader::dotenv), such as `.env` outside
+///    of production
+/// 5. the command-line arguments, when loaded with
+///    [`load_with_args`](AppConfig::load_with_args) or [`Loader::args`]
+/// 6. the overrides given to the [`Loader`]
+///
+/// The config file may be split into profiles, by giving it a top-level
+/// `default` table: the values of the active profile's table override those
+/// of the `default` table. The active profile is selected with the
+/// `<PREFIX>PROFILE` environment variable or the `--profile` flag.
+///
+/// Once merged, the string values of the config file and of the directory
+/// trees may reference other keys and environment variables, e.g.
+/// `"redis://${REDIS_HOST}:${cache.port}/0"`, with a default in
+/// `${VAR:-default}`; `$$` is a literal `$`. The values of the other layers
+/// and of [`Secret`] fields are taken as is.
+///
+/// Once loaded, the configuration is checked against the constraints of its
+/// [fields](Field::constraints) and then [validated](AppConfig::validate).
 pub trait AppConfig: DeserializeOwned + Debug {
     const PACKAGE: &'static str;
 
+    /// The prefix of the environment variables, used as is.
+    ///
+    /// When it is `None`, the prefix is the [`PACKAGE`](AppConfig::PACKAGE)
+    /// made safe to set from shells, see [`env_prefix`].
+    const ENV_PREFIX: Option<&'static str> = None;
+
+    /// The separator of nested keys in environment variables.
+    ///
+    /// With the default `__`, `database.pool.size` is set by
+    /// `<PREFIX>DATABASE__POOL__SIZE`.
+    const ENV_SEPARATOR: &'static str = "__";
+
+    /// The profiles under which the `.env` file is not read implicitly.
+    ///
+    /// Profiles are compared case-insensitively. See [`Loader::dotenv`].
+    const PRODUCTION_PROFILES: &'static [&'static str] = &["prod", "production"];
+
+    /// How the keys that do not map to any field are handled, such as a
+    /// misspelled `<PREFIX>LOG_LEVLE` variable or config file key.
+    ///
+    /// Every layer is checked, and the report of a key holds the nearest
+    /// valid key. Keys are only checked if the configuration describes its
+    /// [fields](AppConfig::fields).
+    const STRICT: Strict = Strict::Off;
+
+    /// Get the fields of the configuration.
+    fn fields() -> Vec<Field> {
+        Vec::new()
+    }
+
+    /// Get the compiled-in default values.
+    ///
+    /// These are the default values of the [fields](AppConfig::fields).
+    fn defaults() -> Dict {
+        field::defaults(&Self::fields())
+    }
+
+    /// Check values that cannot be checked on their own, such as a minimum
+    /// that must not exceed a maximum.
+    ///
+    /// This is called after the configuration is loaded.
+    fn validate(&self) -> std::result::Result<(), Vec<Violation>> {
+        Ok(())
+    }
+
+    /// Get a loader for the configuration.
+    fn loader() -> Loader<Self> {
+        Loader::new()
+    }
+
+    /// Report a warning found while the configuration is loaded, such as an
+    /// unknown key under [`Strict::Warn`].
+    ///
+    /// With the `tracing` feature, this emits a warning event by default;
+    /// without it, this does nothing.
+    fn warn(issue: &Issue) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(package = Self::PACKAGE, "{}", issue);
+        #[cfg(not(feature = "tracing"))]
+        let _ = issue;
+    }
+
     /// Load the configuration.
     fn load() -> Result<Self> {
-        let provider = figment::providers::Env::prefixed(Self::PACKAGE);
-        match figment::Figment::new().join(provider).extract() {
-            Ok(value) => Ok(value),
-            Err(e) => Err(AppConfigError::new(e)),
-        }
+        Self::loader().load()
     }
-}
 
-/// Error that can be returned by the `AppConfig` trait.
-#[derive(Debug)]
-pub struct AppConfigError<T> {
-    inner: figment::Error,
-    _phantom: std::marker::PhantomData<T>,
-}
+    /// Load the configuration, along with its warnings instead of reporting
+    /// them to [`warn`](AppConfig::warn).
+    fn load_with_warnings() -> Result<(Self, Vec<Issue>), Self> {
+        Self::loader().load_with_warnings()
+    }
 
-impl<T> AppConfigError<T> {
-    pub(crate) fn new(inner: figment::Error) -> Self {
-        Self {
-            inner,
-            _phantom: std::marker::PhantomData,
-        }
+    /// Load the configuration with the command-line arguments of the process
+    /// as flags, e.g. `--server.port 8080`.
+    ///
+    /// Unknown flags are rejected. If help was requested with `--help`, the
+    /// error holds the help text in [`ArgsError::Help`]:
+    ///
+    /// ```no_run
+    /// # #[macro_use] extern crate blockz_config;
+    /// use blockz_config::AppConfig;
+    /// use blockz_config::ArgsError;
+    ///
+    /// config!("APP_" {
+    ///     port: u16 = 8080,
+    /// });
+    ///
+    /// # fn main() {
+    /// let config = match Config::load_with_args() {
+    ///     Ok(config) => config,
+    ///     Err(e) => {
+    ///         eprintln!("{}", e);
+    ///         let help = matches!(e.args(), Some(ArgsError::Help(_)));
+    ///         std::process::exit(if help { 0 } else { 2 });
+    ///     }
+    /// };
+    /// # }
+    /// ```
+    fn load_with_args() -> Result<Self> {
+        Self::loader().env_args().load()
     }
-}
 
-impl<T: AppConfig> Display for AppConfigError<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if f.alternate() {
-            f.write_fmt(format_args!(
-                "{}: app config error: {:#}",
-                T::PACKAGE,
-                self.inner
-            ))
-        } else {
-            f.write_fmt(format_args!(
-                "{}: app config error: {}",
-                T::PACKAGE,
-                self.inner
-            ))
-        }
+    /// Load the configuration with the command-line arguments of the process
+    /// as flags, along with its warnings instead of reporting them to
+    /// [`warn`](AppConfig::warn).
+    ///
+    /// See [`load_with_args`](AppConfig::load_with_args).
+    fn load_with_args_and_warnings() -> Result<(Self, Vec<Issue>), Self> {
+        Self::loader().env_args().load_with_warnings()
+    }
+
+    /// Check that a profile of the config file sets every field that must be
+    /// set, e.g. to catch a missing `prod` key at startup.
+    ///
+    /// See [`Loader::check_profile`].
+    fn check_profile(profile: &str) -> Result<(), Self> {
+        Self::loader().check_profile(profile)
     }
-}
 
-impl<T: AppConfig> Error for AppConfigError<T> {
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        Some(&self.inner)
+    /// Explain where every effective value of the configuration comes from:
+    /// its layer and the file and line, environment variable or flag that
+    /// set it.
+    ///
+    /// Secret values are redacted, as are the values of keys without a
+    /// declared [field](AppConfig::fields), which may be secrets. The report
+    /// is printed with `Display`.
+    fn explain() -> Result<Explanation, Self> {
+        Self::loader().explain()
+    }
+
+    /// Load the configuration and keep reloading it when its sources change.
+    ///
+    /// See [`Reloader`](reload::Reloader).
+    #[cfg(feature = "reload")]
+    fn reloading(
+        options: reload::ReloadOptions,
+    ) -> Result<(reload::Reloader<Self>, reload::ReloadWorker), Self>
+    where
+        Self: Send + Sync + 'static,
+    {
+        Self::loader().reloading(options)
     }
 }
 
 /// Macro that aids in declaratively creating the configuration object for an
 /// application.
+///
+/// The macro generates a `Config` struct that implements [`AppConfig`],
+/// [`Section`] and `Default`, with a field for every declared field:
+///
+/// ```
+/// # #[macro_use] extern crate blockz_config;
+/// config!("APP_" {
+///     /// The port to listen on.
+///     port: u16 = 8080,
+///     /// The name of the service.
+///     name: String = "app".into(),
+///     /// The database settings.
+///     database: Database {
+///         #[env = "DATABASE_URL"]
+///         url: String,
+///         pool_size: u32 = 4,
+///     },
+/// });
+/// # fn main() {
+/// # let config = Config::default();
+/// # assert_eq!(config.port, 8080);
+/// # assert_eq!(config.database.pool_size, 4);
+/// # }
+/// ```
+///
+/// Every field is declared as `name: Type`, optionally followed by
+/// `= default`, an expression of the field type. Defaults make up the
+/// [defaults layer](AppConfig::defaults), so a field without a default must be
+/// set by another layer. Fields without a default are set to
+/// `Default::default()` in the `Default` impl.
+///
+/// A field declared as `name: Type { ... }` is a section: a struct named
+/// `Type` is generated for it with the fields in the braces.
+///
+/// Fields may be preceded by doc comments and, after them, by an
+/// `#[env = "NAME"]` attribute that names an environment variable that sets
+/// the field besides the one under the package prefix, and by any number of
+/// `#[validate(...)]` attributes that add [constraints](Constraint) to the
+/// field:
+///
+/// - `#[validate(range(1..=65535))]`
+/// - `#[validate(non_empty)]`
+/// - `#[validate(regex = "^[a-z]+$")]`
+/// - `#[validate(url("postgres", "postgresql"))]`
+/// - `#[validate(one_of("debug", "info"))]`
+/// - `#[validate(custom = path::to::check)]`, where `check` is a
+///   `fn(&Type) -> Result<(), String>`
+///
+/// Checks across fields are given as `config!("APP_", validate = path { ... })`,
+/// where `path` is the [`AppConfig::validate`] implementation.
+///
+/// The package name is normalised into the [prefix](env_prefix) of the
+/// environment variables, so `config!("my-app" { ... })` reads `MY_APP_PORT`.
+/// Without a package name, as in `config!()` or `config!({ ... })`, it is the
+/// name of the crate. The prefix is given as is with
+/// `config!("my-app", prefix = "APP_" { ... })`, before any `validate`.
+///
+/// Keys that do not map to any field are reported with
+/// `config!("APP_", strict = Warn { ... })` or rejected with
+/// `strict = Deny`, after any `prefix` and `validate`; see
+/// [`AppConfig::STRICT`].
 #[macro_export]
 macro_rules! config {
+    () => {
+        $crate::config!({});
+    };
+    ({ $($body: tt)* }) => {
+        $crate::config!(@config (::core::env!("CARGO_PKG_NAME")) [] [] [] { $($body)* });
+    };
     ($package: literal) => {
-        #[doc = "Application configuration for `"]
-        #[doc = $package]
-        #[doc = "`."]
-        #[derive(Debug, ::serde::Deserialize)]
-        pub struct Config {}
+        $crate::config!($package {});
+    };
+    (
+        $package: literal
+        $(, prefix = $prefix: literal)?
+        $(, validate = $validate: path)?
+        $(, strict = $strict: ident)?
+        { $($body: tt)* }
+    ) => {
+        $crate::config!(
+            @config ($package) [$($prefix)?] [$($validate)?] [$($strict)?] { $($body)* }
+        );
+    };
+
+    // generate the configuration
+    (@config
+        ($package: expr)
+        [$($prefix: literal)?]
+        [$($validate: path)?]
+        [$($strict: ident)?]
+        { $($body: tt)* }
+    ) => {
+        // the derives find serde through this, whatever the crate is named
+        #[doc(hidden)]
+        use $crate::__private::serde as __blockz_config_serde;
+
+        $crate::config!(
+            @struct
+            [
+                "Application configuration for `",
+                $package,
+                "`."
+            ]
+            Config { $($body)* }
+        );
 
-        impl ::blockz_config::AppConfig for Config {
+        impl $crate::AppConfig for Config {
             const PACKAGE: &'static str = $package;
+            $(const ENV_PREFIX: ::core::option::Option<&'static str> =
+                ::core::option::Option::Some($prefix);)?
+            $(const STRICT: $crate::Strict = $crate::Strict::$strict;)?
+
+            fn fields() -> ::std::vec::Vec<$crate::Field> {
+                <Self as $crate::Section>::fields()
+            }
+
+            $(
+                fn validate(
+                    &self,
+                ) -> ::core::result::Result<(), ::std::vec::Vec<$crate::Violation>> {
+                    $validate(self)
+                }
+            )?
+        }
+    };
+
+    // generate a struct and the structs of its sections
+    (@struct [$($doc: expr),*] $name: ident { $($body: tt)* }) => {
+        $crate::config!(@munch [$($doc),*] $name [] $($body)*);
+    };
+
+    // all the fields have been parsed
+    (@munch [$($sdoc: expr),*] $sname: ident [$({
+        [$($doc: literal)*]
+        [$($env: literal)?]
+        [$([$($constraint: tt)*])*]
+        $field: ident
+        ($ty: ty)
+        [$($default: expr)?]
+        [$([$($section_doc: literal)*] $section: ident { $($inner: tt)* })?]
+    })*]) => {
+        #[doc = ::core::concat!($($sdoc),*)]
+        #[derive(
+            ::core::fmt::Debug,
+            $crate::__private::serde::Deserialize,
+            $crate::__private::serde::Serialize,
+        )]
+        #[serde(crate = "self::__blockz_config_serde")]
+        pub struct $sname {
+            $(
+                $(#[doc = $doc])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl ::core::default::Default for $sname {
+            fn default() -> Self {
+                Self {
+                    $($field: $crate::config!(@default ($ty) $($default)?),)*
+                }
+            }
         }
+
+        impl $crate::Section for $sname {
+            fn fields() -> ::std::vec::Vec<$crate::Field> {
+                ::std::vec![$(
+                    $crate::Field::new(
+                        ::core::stringify!($field),
+                        ::core::stringify!($ty),
+                    )
+                    .with_docs(&[$($doc),*])
+                    $(.with_env($env))?
+                    $(.with_constraint($crate::config!(@constraint ($ty) $($constraint)*)))*
+                    $(.with_default($crate::__private::default_value::<$ty>($default)))?
+                    $(.with_fields(<$section as $crate::Section>::fields()))?,
+                )*]
+            }
+        }
+
+        $($(
+            $crate::config!(
+                @struct
+                [$($section_doc),*]
+                $section { $($inner)* }
+            );
+        )?)*
+    };
+
+    // a section
+    (@munch [$($sdoc: expr),*] $sname: ident [$($fields: tt)*]
+        $(#[doc = $doc: literal])*
+        $(#[env = $env: literal])?
+        $field: ident : $section: ident { $($inner: tt)* }
+        $(, $($rest: tt)*)?
+    ) => {
+        $crate::config!(
+            @munch [$($sdoc),*] $sname [$($fields)* {
+                [$($doc)*]
+                [$($env)?]
+                []
+                $field
+                ($section)
+                []
+                [[$($doc)*] $section { $($inner)* }]
+            }]
+            $($($rest)*)?
+        );
+    };
+
+    // a field
+    (@munch [$($sdoc: expr),*] $sname: ident [$($fields: tt)*]
+        $(#[doc = $doc: literal])*
+        $(#[env = $env: literal])?
+        $(#[validate($($constraint: tt)*)])*
+        $field: ident : $ty: ty $(= $default: expr)?
+        $(, $($rest: tt)*)?
+    ) => {
+        $crate::config!(
+            @munch [$($sdoc),*] $sname [$($fields)* {
+                [$($doc)*]
+                [$($env)?]
+                [$([$($constraint)*])*]
+                $field
+                ($ty)
+                [$($default)?]
+                []
+            }]
+            $($($rest)*)?
+        );
+    };
+
+    // the value of a field in the `Default` impl
+    (@default ($ty: ty)) => {
+        ::core::default::Default::default()
+    };
+    (@default ($ty: ty) $default: expr) => {
+        $default
+    };
+
+    // a constraint in a `#[validate(...)]` attribute
+    (@constraint ($ty: ty) range($range: expr)) => {
+        $crate::Constraint::range($range)
+    };
+    (@constraint ($ty: ty) non_empty) => {
+        $crate::Constraint::NonEmpty
+    };
+    (@constraint ($ty: ty) regex = $pattern: literal) => {
+        $crate::Constraint::Regex($pattern)
+    };
+    (@constraint ($ty: ty) url($($scheme: literal),* $(,)?)) => {
+        $crate::Constraint::Url(&[$($scheme),*])
+    };
+    (@constraint ($ty: ty) one_of($($value: literal),* $(,)?)) => {
+        $crate::Constraint::OneOf(&[$($value),*])
+    };
+    (@constraint ($ty: ty) custom = $check: path) => {
+        $crate::Constraint::Custom(|value| $crate::__private::custom::<$ty>(value, $check))
     };
 }
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+
+    use figment::value::Value;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    /// Convert the default value of a field into a figment value.
+    pub fn default_value<T: Serialize>(value: T) -> Value {
+        Value::serialize(value).expect("default value must be serializable")
+    }
+
+    /// Run a custom check on the value of a field.
+    pub fn custom<T: DeserializeOwned>(
+        value: &Value,
+        check: fn(&T) -> Result<(), String>,
+    ) -> Result<(), String> {
+        // values that do not deserialize are reported by the extraction
+        match value.deserialize::<T>() {
+            Ok(value) => check(&value),
+            Err(_) => Ok(()),
+        }
+    }
+}
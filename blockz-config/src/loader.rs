@@ -0,0 +1,839 @@
+//! Layered loading of app configurations.
+
+use std::fmt;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use figment::providers::Data;
+use figment::providers::Env;
+use figment::providers::Format;
+use figment::providers::Json;
+use figment::providers::Serialized;
+use figment::providers::Toml;
+use figment::providers::Yaml;
+use figment::value::Dict;
+use figment::value::Map;
+use figment::Figment;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+use figment::Source;
+use serde::Serialize;
+
+use crate::cli;
+use crate::cli::Args;
+use crate::dotenv::DotEnv;
+use crate::env;
+use crate::env::EnvVars;
+use crate::explain::Explanation;
+use crate::field;
+use crate::interpolate;
+use crate::issue;
+use crate::issue::Issue;
+use crate::issue::Strict;
+#[cfg(feature = "remote")]
+use crate::remote::Remote;
+use crate::secret;
+use crate::secret::SecretFiles;
+use crate::tree::DirectoryTree;
+use crate::validate;
+use crate::AppConfig;
+use crate::AppConfigError;
+use crate::Field;
+use crate::Result;
+
+/// The key of the environment layer that holds the path of the config file.
+///
+/// For a package with the `APP_` prefix, this is the `APP_CONFIG` variable.
+pub const CONFIG_FILE_KEY: &str = "config";
+
+/// The key of the environment layer that holds the name of the active
+/// profile.
+///
+/// For a package with the `APP_` prefix, this is the `APP_PROFILE` variable.
+/// The profile can also be given as the `--profile` flag.
+pub const PROFILE_KEY: &str = "profile";
+
+/// The config files that are looked up in the working directory, in order,
+/// when no path is given.
+pub const CONFIG_FILE_NAMES: &[&str] = &["config.toml", "config.yaml", "config.yml", "config.json"];
+
+/// The env file that is looked up in the working directory when no path is
+/// given.
+pub const DOTENV_FILE: &str = ".env";
+
+/// A layer of the configuration stack.
+///
+/// Layers are listed from the lowest to the highest precedence: a value from
+/// a layer overrides the values for the same key from the layers before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Layer {
+    /// The compiled-in defaults, see [`AppConfig::defaults`].
+    Defaults,
+    /// The optional config file and the directory trees given to the
+    /// [`Loader`].
+    File,
+    /// The remotes given to the [`Loader`].
+    Remote,
+    /// The environment variables under the package prefix, and the
+    /// variables of the env files, see [`Loader::dotenv`].
+    Env,
+    /// The command-line arguments given to the [`Loader`].
+    Args,
+    /// The overrides given to the [`Loader`].
+    Overrides,
+}
+
+impl Layer {
+    /// Every layer, from the lowest to the highest precedence.
+    pub const ALL: &'static [Layer] = &[
+        Layer::Defaults,
+        Layer::File,
+        Layer::Remote,
+        Layer::Env,
+        Layer::Args,
+        Layer::Overrides,
+    ];
+
+    /// Get the name of this layer.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Defaults => "defaults",
+            Self::File => "config file",
+            Self::Remote => "remote",
+            Self::Env => "environment",
+            Self::Args => "command line",
+            Self::Overrides => "overrides",
+        }
+    }
+
+    /// Get the layer with a name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|layer| layer.name() == name)
+    }
+}
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Loads an app configuration from a stack of layers.
+///
+/// See [`Layer`] for the layers and their precedence.
+pub struct Loader<T> {
+    file: Option<PathBuf>,
+    directories: Vec<PathBuf>,
+    dotenv: Vec<PathBuf>,
+    dotenv_override: bool,
+    profile: Option<Profile>,
+    args: Option<Vec<String>>,
+    #[cfg(feature = "remote")]
+    remotes: Vec<Remote>,
+    overrides: Vec<Box<dyn Provider + Send + Sync>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: AppConfig> Loader<T> {
+    /// Create a new `Loader`.
+    pub fn new() -> Self {
+        Self {
+            file: None,
+            directories: Vec::new(),
+            dotenv: Vec::new(),
+            dotenv_override: false,
+            profile: None,
+            args: None,
+            #[cfg(feature = "remote")]
+            remotes: Vec::new(),
+            overrides: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Read the config file from a path.
+    ///
+    /// The file must exist. Without a path, the path is taken from the
+    /// [`CONFIG_FILE_KEY`] environment variable or, failing that, from the
+    /// first of the [`CONFIG_FILE_NAMES`] that exists.
+    pub fn file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Read values from a directory tree with a file per key, such as a
+    /// mounted Kubernetes ConfigMap, see [`DirectoryTree`].
+    ///
+    /// Directory trees are applied after the config file, in the order they
+    /// are given.
+    pub fn directory<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.directories.push(path.into());
+        self
+    }
+
+    /// Read environment variables from an env file, e.g. for local
+    /// development.
+    ///
+    /// The file must exist. Env files are read in the order they are given,
+    /// each overriding the ones before it. Without a path, the
+    /// [`DOTENV_FILE`] in the working directory is read if it exists, unless
+    /// the active profile is one of the
+    /// [`PRODUCTION_PROFILES`](AppConfig::PRODUCTION_PROFILES).
+    ///
+    /// Lines are `NAME=value` pairs, optionally preceded by `export`, with
+    /// single- or double-quoted values that may span lines, and `#` comments.
+    ///
+    /// Unlike in the environment, `<VAR>_FILE` variables do not name files
+    /// that hold values: those of declared fields are ignored, and the others
+    /// set their own key.
+    pub fn dotenv<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.dotenv.push(path.into());
+        self
+    }
+
+    /// Let the variables of env files override those of the environment.
+    ///
+    /// By default, a variable that is set in the environment wins over the
+    /// same variable in an env file.
+    pub fn dotenv_override(mut self, dotenv_override: bool) -> Self {
+        self.dotenv_override = dotenv_override;
+        self
+    }
+
+    /// Select the active profile.
+    ///
+    /// Without a profile, it is taken from the `--profile` flag or the
+    /// [`PROFILE_KEY`] environment variable, and defaults to
+    /// [`Profile::Default`].
+    pub fn profile<P: Into<Profile>>(mut self, profile: P) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Set values from command-line arguments, without the program name.
+    ///
+    /// Every key path is a flag, e.g. `--server.port 8080`; see
+    /// [`help`](crate::help) for the flags of a configuration.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = Some(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set values from the command-line arguments of the process.
+    pub fn env_args(self) -> Self {
+        self.args(std::env::args().skip(1))
+    }
+
+    /// Override the value of a key.
+    ///
+    /// Nested keys are separated by dots, e.g. `server.port`.
+    pub fn set<V: Serialize + Send + Sync + 'static>(self, key: &str, value: V) -> Self {
+        self.overrides(Serialized::global(key, value))
+    }
+
+    /// Read values from a remote.
+    ///
+    /// Remotes are applied in the order they are given, above the config
+    /// file. Their values are fetched with [`Remote::refresh`], e.g. by
+    /// [`load_async`](Loader::load_async).
+    #[cfg(feature = "remote")]
+    pub fn remote(mut self, remote: Remote) -> Self {
+        self.remotes.push(remote);
+        self
+    }
+
+    /// Override values with a provider.
+    ///
+    /// Overrides are applied in the order they are given.
+    pub fn overrides<P: Provider + Send + Sync + 'static>(mut self, provider: P) -> Self {
+        self.overrides.push(Box::new(provider));
+        self
+    }
+
+    /// Get the path of the config file that will be read, if any.
+    pub fn config_file(&self) -> Result<Option<PathBuf>, T> {
+        if let Some(path) = &self.file {
+            return existing(path).map(Some);
+        }
+
+        let from_env = env::prefixed::<T>()
+            .iter()
+            .find(|(key, _)| key.as_str() == CONFIG_FILE_KEY)
+            .map(|(_, value)| PathBuf::from(value));
+        if let Some(path) = from_env {
+            return existing(&path).map(Some);
+        }
+
+        Ok(CONFIG_FILE_NAMES
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.is_file()))
+    }
+
+    /// Build the figment that holds the defaults and the config file.
+    fn file_figment(&self) -> Result<Figment, T> {
+        let defaults = Serialized::defaults(T::defaults());
+        let mut figment = Figment::new().merge(Named::new(Layer::Defaults, &defaults));
+
+        if let Some(path) = self.config_file()? {
+            let file = file_provider::<T>(&path)?;
+            figment = figment.merge(Named::new(Layer::File, &*file));
+        }
+        for path in &self.directories {
+            figment = figment.merge(Named::new(Layer::File, &DirectoryTree::new(path)));
+        }
+        Ok(figment)
+    }
+
+    /// Build the figment that holds every layer, with the active profile
+    /// selected.
+    ///
+    /// The environment, the command-line arguments and the overrides apply to
+    /// every profile.
+    pub fn figment(&self) -> Result<Figment, T> {
+        let mut figment = self.file_figment()?;
+
+        #[cfg(feature = "remote")]
+        for remote in &self.remotes {
+            figment = figment.merge(Named::new(Layer::Remote, remote));
+        }
+
+        let args = match &self.args {
+            Some(args) => Some(Args::parse::<T>(args).map_err(AppConfigError::from_args)?),
+            None => None,
+        };
+        let profile = self.active_profile(args.as_ref());
+
+        let fields = T::fields();
+        let dotenv = DotEnv::read::<T>(&self.dotenv_paths(&profile))
+            .map_err(|e| AppConfigError::new(figment::Error::from(e)))?;
+        if !self.dotenv_override {
+            figment = merge_dotenv(figment, &dotenv, &fields);
+        }
+
+        let paths = field::paths(&fields);
+        let env = env::prefixed::<T>()
+            .ignore(&[CONFIG_FILE_KEY, PROFILE_KEY])
+            .filter(move |key| secret::declared_file_key(key.as_str(), &paths).is_none())
+            .global();
+        let env = EnvVars::new(env, &fields);
+        figment = figment.merge(
+            Named::new(Layer::Env, &env).env_names(|keys| env::env_var::<T>(&keys.join("."))),
+        );
+
+        let vars = field::env_vars(&fields);
+        if !vars.is_empty() {
+            let aliases = EnvVars::new(env_aliases(vars.clone()), &fields);
+            figment = figment.merge(Named::new(Layer::Env, &aliases).env_names(move |keys| {
+                let path = keys.join(".");
+                vars.iter()
+                    .find(|(_, var_path)| *var_path == path)
+                    .map(|(var, _)| var.to_string())
+                    .unwrap_or(path)
+            }));
+        }
+
+        let files = SecretFiles::new::<T>();
+        if !files.is_empty() {
+            let vars = files.clone();
+            figment = figment.merge(Named::new(Layer::Env, &files).env_names(move |keys| {
+                let path = keys.join(".");
+                vars.var(&path).map(str::to_string).unwrap_or(path)
+            }));
+        }
+
+        if self.dotenv_override {
+            figment = merge_dotenv(figment, &dotenv, &fields);
+        }
+
+        if let Some(args) = &args {
+            let args = EnvVars::new(args, &fields);
+            figment = figment.merge(
+                Named::new(Layer::Args, &args).env_names(|keys| cli::flag_name(&keys.join("."))),
+            );
+        }
+
+        for provider in &self.overrides {
+            figment = figment.merge(Named::new(Layer::Overrides, &**provider));
+        }
+
+        interpolated::<T>(figment.select(profile))
+    }
+
+    /// Get the active profile, given the parsed command-line arguments.
+    fn active_profile(&self, args: Option<&Args>) -> Profile {
+        self.profile
+            .clone()
+            .or_else(|| args.and_then(Args::profile).map(Profile::new))
+            .or_else(|| {
+                env::prefixed::<T>()
+                    .iter()
+                    .find(|(key, _)| key.as_str() == PROFILE_KEY)
+                    .map(|(_, value)| Profile::new(&value))
+            })
+            .unwrap_or(Profile::Default)
+    }
+
+    /// Get the paths of the env files to read under the active profile.
+    fn dotenv_paths(&self, profile: &Profile) -> Vec<PathBuf> {
+        if !self.dotenv.is_empty() {
+            return self.dotenv.clone();
+        }
+        let production = T::PRODUCTION_PROFILES
+            .iter()
+            .any(|name| profile.as_str().as_str().eq_ignore_ascii_case(name));
+        let path = PathBuf::from(DOTENV_FILE);
+        if production || !path.is_file() {
+            Vec::new()
+        } else {
+            vec![path]
+        }
+    }
+
+    /// Check that a profile of the config file, on top of the defaults, sets
+    /// every field that must be set, with valid values.
+    ///
+    /// The environment, the command-line arguments and the overrides are not
+    /// taken into account, so that a profile can be checked anywhere, e.g.
+    /// `prod` at the startup of a development build.
+    pub fn check_profile<P: Into<Profile>>(&self, profile: P) -> Result<(), T> {
+        extract::<T>(&interpolated::<T>(self.file_figment()?.select(profile))?).map(drop)
+    }
+
+    /// Load the configuration.
+    ///
+    /// The configuration is validated after it is extracted, see
+    /// [`AppConfig::validate`]. Every invalid value is reported in the error,
+    /// and every warning to [`AppConfig::warn`].
+    pub fn load(&self) -> Result<T> {
+        extract(&self.figment()?)
+    }
+
+    /// Load the configuration, along with its warnings instead of reporting
+    /// them to [`AppConfig::warn`], such as the unknown keys found when
+    /// [`AppConfig::STRICT`] is [`Strict::Warn`].
+    ///
+    /// See [`Loader::load`].
+    pub fn load_with_warnings(&self) -> Result<(T, Vec<Issue>), T> {
+        extract_with_warnings(&self.figment()?)
+    }
+
+    /// Fetch the values of the remotes, then load the configuration.
+    ///
+    /// A remote that is unavailable falls back to its last values, see
+    /// [`Remote::refresh`].
+    #[cfg(feature = "remote")]
+    pub async fn load_async(&self) -> Result<T> {
+        for remote in &self.remotes {
+            remote.refresh().await.map_err(|e| {
+                AppConfigError::new(figment::Error::from(format!(
+                    "failed to fetch `{}`: {}",
+                    remote.name(),
+                    e
+                )))
+            })?;
+        }
+        self.load()
+    }
+
+    /// Explain where every effective value comes from.
+    ///
+    /// The values are not validated, so that invalid values can be explained.
+    pub fn explain(&self) -> Result<Explanation, T> {
+        Explanation::new(&self.figment()?, &T::fields())
+    }
+
+    /// Load the configuration along with the values it was extracted from.
+    #[cfg(feature = "reload")]
+    pub(crate) fn load_values(&self) -> Result<(T, Dict), T> {
+        let figment = self.figment()?;
+        let config = extract(&figment)?;
+        let values = figment.extract().map_err(AppConfigError::new)?;
+        Ok((config, values))
+    }
+
+    /// Get the paths of the files the configuration is read from.
+    #[cfg(feature = "reload")]
+    pub(crate) fn watched_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.config_file().ok().flatten().into_iter().collect();
+        for path in &self.directories {
+            paths.extend(DirectoryTree::new(path).paths());
+        }
+        paths.extend(SecretFiles::new::<T>().paths());
+        let args = self
+            .args
+            .as_ref()
+            .and_then(|args| Args::parse::<T>(args).ok());
+        paths.extend(self.dotenv_paths(&self.active_profile(args.as_ref())));
+        paths
+    }
+
+    /// Get a number that changes whenever the values of a remote change.
+    #[cfg(all(feature = "reload", feature = "remote"))]
+    pub(crate) fn revision(&self) -> u64 {
+        self.remotes.iter().map(Remote::revision).sum()
+    }
+
+    /// Get a number that changes whenever the values of a remote change.
+    #[cfg(all(feature = "reload", not(feature = "remote")))]
+    pub(crate) fn revision(&self) -> u64 {
+        0
+    }
+
+    /// Load the configuration and keep reloading it when its sources change.
+    ///
+    /// See [`Reloader`](crate::reload::Reloader).
+    #[cfg(feature = "reload")]
+    pub fn reloading(
+        self,
+        options: crate::reload::ReloadOptions,
+    ) -> Result<(crate::reload::Reloader<T>, crate::reload::ReloadWorker), T>
+    where
+        T: Send + Sync + 'static,
+    {
+        crate::reload::Reloader::new(self, options)
+    }
+}
+
+impl<T: AppConfig> Default for Loader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extract the configuration from `figment` and validate it, reporting its
+/// warnings.
+fn extract<T: AppConfig>(figment: &Figment) -> Result<T> {
+    let (config, warnings) = extract_with_warnings::<T>(figment)?;
+    for issue in &warnings {
+        T::warn(issue);
+    }
+    Ok(config)
+}
+
+/// Extract the configuration from `figment` and validate it, along with the
+/// unknown keys that do not fail loading.
+fn extract_with_warnings<T: AppConfig>(figment: &Figment) -> Result<(T, Vec<Issue>), T> {
+    let config: T = figment.extract().map_err(|err| {
+        let issues = issue::find::<T>(figment, &err);
+        if issues.is_empty() {
+            AppConfigError::new(err)
+        } else {
+            AppConfigError::from_issues(issues, err)
+        }
+    })?;
+
+    let mut warnings = Vec::new();
+    if T::STRICT != Strict::Off {
+        let unknown = issue::unknown::<T>(figment);
+        if T::STRICT == Strict::Deny && !unknown.is_empty() {
+            return Err(AppConfigError::from_unknown(unknown));
+        }
+        warnings = unknown;
+    }
+
+    let mut violations = validate::check_fields(figment, &T::fields());
+    if let Err(invalid) = config.validate() {
+        violations.extend(invalid);
+    }
+    if violations.is_empty() {
+        Ok((config, warnings))
+    } else {
+        let violations = violations
+            .into_iter()
+            .map(|violation| violation.locate(figment))
+            .collect();
+        Err(AppConfigError::invalid(violations))
+    }
+}
+
+/// Resolve the references inside the values of `figment`.
+fn interpolated<T: AppConfig>(figment: Figment) -> Result<Figment, T> {
+    interpolate::interpolate(figment, &T::fields()).map_err(AppConfigError::from_interpolation)
+}
+
+/// Merge the variables of env files that set fields into `figment`.
+fn merge_dotenv(figment: Figment, dotenv: &DotEnv, fields: &[Field]) -> Figment {
+    if dotenv.is_empty() {
+        return figment;
+    }
+    let origins = dotenv.clone();
+    let vars = EnvVars::new(dotenv.clone(), fields);
+    figment.merge(Named::new(Layer::Env, &vars).env_names(move |keys| {
+        let path = keys.join(".");
+        origins.origin(&path).map(str::to_string).unwrap_or(path)
+    }))
+}
+
+/// Get the provider for the environment variables that set fields directly.
+fn env_aliases(vars: Vec<(&'static str, String)>) -> Env {
+    Env::raw()
+        .filter_map(move |key| {
+            vars.iter()
+                .find(|(var, _)| key == *var)
+                .map(|(_, path)| path.clone().into())
+        })
+        .global()
+}
+
+/// Check that the config file at `path` exists.
+fn existing<T>(path: &Path) -> Result<PathBuf, T> {
+    if path.is_file() {
+        Ok(path.to_path_buf())
+    } else {
+        Err(AppConfigError::new(figment::Error::from(format!(
+            "config file {} does not exist",
+            path.display()
+        ))))
+    }
+}
+
+/// Get the provider for the config file at `path`, by its extension.
+fn file_provider<T>(path: &Path) -> Result<Box<dyn Provider>, T> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    match extension {
+        Some("toml") => Ok(profiled(Toml::file_exact(path))),
+        Some("yaml") | Some("yml") => Ok(profiled(Yaml::file_exact(path))),
+        Some("json") => Ok(profiled(Json::file_exact(path))),
+        _ => Err(AppConfigError::new(figment::Error::from(format!(
+            "config file {} is not a TOML, YAML or JSON file",
+            path.display()
+        )))),
+    }
+}
+
+/// Split a config file into profiles if it has a top-level `default` table.
+///
+/// The tables of a split file are profiles: the `default` profile holds the
+/// values of every profile, which the selected profile overrides.
+fn profiled<F: Format + 'static>(file: Data<F>) -> Box<dyn Provider> {
+    let has_profiles = file.data().ok().is_some_and(|data| {
+        data.get(&Profile::Default)
+            .and_then(|dict| dict.get(Profile::Default.as_str().as_str()))
+            .is_some_and(|value| value.as_dict().is_some())
+    });
+    if has_profiles {
+        Box::new(file.nested())
+    } else {
+        Box::new(file)
+    }
+}
+
+/// A provider that reports the layer it belongs to as its name.
+struct Named<'a> {
+    layer: Layer,
+    provider: &'a dyn Provider,
+    env_names: Option<EnvNames>,
+}
+
+/// Maps a key path to the name of the environment variable it comes from.
+type EnvNames = Arc<dyn Fn(&[&str]) -> String + Send + Sync>;
+
+impl<'a> Named<'a> {
+    fn new(layer: Layer, provider: &'a dyn Provider) -> Self {
+        Self {
+            layer,
+            provider,
+            env_names: None,
+        }
+    }
+
+    /// Report keys as the environment variables they come from.
+    fn env_names<F>(mut self, env_names: F) -> Self
+    where
+        F: Fn(&[&str]) -> String + Send + Sync + 'static,
+    {
+        self.env_names = Some(Arc::new(env_names));
+        self
+    }
+}
+
+impl Provider for Named<'_> {
+    fn metadata(&self) -> Metadata {
+        let mut metadata = self.provider.metadata();
+        metadata.name = self.layer.name().into();
+        // the location of the code that created the provider means nothing to
+        // the user
+        if let Some(Source::Code(_)) = metadata.source {
+            metadata.source = None;
+        }
+        match &self.env_names {
+            Some(env_names) => {
+                let env_names = env_names.clone();
+                metadata.interpolater(move |_: &Profile, keys: &[&str]| env_names(keys))
+            }
+            None => metadata,
+        }
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        self.provider.data()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use figment::util::map;
+    use figment::value::Dict;
+    use figment::value::Value;
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::Layer;
+    use crate::AppConfig;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct TestConfig {
+        host: String,
+        port: u16,
+        debug: bool,
+        workers: u8,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn defaults() -> Dict {
+            map![
+                "host".to_string() => Value::from("localhost"),
+                "port".to_string() => Value::from(80),
+                "debug".to_string() => Value::from(false),
+                "workers".to_string() => Value::from(1),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_loader_defaults() {
+        Jail::expect_with(|_| {
+            let config = TestConfig::load().unwrap();
+            assert_eq!(config.host, "localhost");
+            assert_eq!(config.port, 80);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_loader_precedence() {
+        Jail::expect_with(|jail| {
+            jail.create_file("config.toml", "host = 'file'\nport = 81\ndebug = true")?;
+            jail.set_env("APP_PORT", 82);
+            jail.set_env("APP_DEBUG", false);
+
+            let config = TestConfig::loader().set("debug", true).load().unwrap();
+            assert_eq!(
+                config,
+                TestConfig {
+                    host: "file".to_string(),
+                    port: 82,
+                    debug: true,
+                    workers: 1,
+                }
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_loader_file_from_env() {
+        Jail::expect_with(|jail| {
+            jail.create_file("config.toml", "port = 81")?;
+            jail.create_file("custom.yaml", "port: 83")?;
+            jail.set_env("APP_CONFIG", "custom.yaml");
+
+            assert_eq!(TestConfig::load().unwrap().port, 83);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_loader_missing_file() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_CONFIG", "missing.json");
+
+            let err = TestConfig::load().unwrap_err();
+            assert!(err.to_string().contains("missing.json"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_loader_error_layer() {
+        Jail::expect_with(|jail| {
+            jail.create_file("config.json", r#"{"port": "high"}"#)?;
+            let err = TestConfig::load().unwrap_err();
+            assert_eq!(err.layer(), Some(Layer::File));
+
+            jail.set_env("APP_PORT", "higher");
+            let err = TestConfig::load().unwrap_err();
+            assert_eq!(err.layer(), Some(Layer::Env));
+            assert!(err.to_string().contains("APP_PORT"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_loader_profiles() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.toml",
+                "[default]\nhost = 'dev'\nworkers = 2\n\n[prod]\nhost = 'prod'\nport = 443",
+            )?;
+
+            let config = TestConfig::load().unwrap();
+            assert_eq!((config.host.as_str(), config.port), ("dev", 80));
+            assert_eq!(config.workers, 2);
+
+            jail.set_env("APP_PROFILE", "prod");
+            let config = TestConfig::load().unwrap();
+            assert_eq!((config.host.as_str(), config.port), ("prod", 443));
+            assert_eq!(config.workers, 2);
+
+            // the environment applies to every profile
+            jail.set_env("APP_PORT", 8443);
+            assert_eq!(TestConfig::load().unwrap().port, 8443);
+
+            let config = TestConfig::loader()
+                .args(vec!["--profile", "default"])
+                .load()
+                .unwrap();
+            assert_eq!(config.host, "dev");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_loader_check_profile() {
+        #[derive(Debug, Deserialize)]
+        struct Required {
+            #[allow(dead_code)]
+            url: String,
+        }
+
+        impl AppConfig for Required {
+            const PACKAGE: &'static str = "APP_";
+        }
+
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                "default: {}\nstaging:\n  url: db\nprod:\n  host: db",
+            )?;
+            jail.set_env("APP_URL", "from-env");
+
+            assert!(Required::check_profile("staging").is_ok());
+            let err = Required::check_profile("prod").unwrap_err();
+            assert!(err.to_string().contains("missing field `url`"));
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,477 @@
+//! Reload app configurations when their sources change.
+//!
+//! A [`Reloader`] holds the last good configuration and publishes every reload
+//! through a [`watch`] channel. The sources are checked by a [`ReloadWorker`],
+//! a future that must be spawned on a tokio runtime.
+
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::sync::Weak;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use figment::value::Dict;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+
+use crate::AppConfig;
+use crate::AppConfigError;
+use crate::Loader;
+use crate::Result;
+
+/// Options for reloading configurations.
+#[derive(Clone, Copy, Debug)]
+pub struct ReloadOptions {
+    interval: Duration,
+    sighup: bool,
+}
+
+impl ReloadOptions {
+    /// Create new options that check the sources every two seconds.
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_secs(2),
+            sighup: false,
+        }
+    }
+
+    /// Set how often the sources are checked for changes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Reload when the process receives `SIGHUP`.
+    ///
+    /// This is ignored on platforms without signals.
+    pub fn sighup(mut self, sighup: bool) -> Self {
+        self.sighup = sighup;
+        self
+    }
+}
+
+impl Default for ReloadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The outcome of a reload.
+pub struct Reload<T> {
+    config: Arc<T>,
+    changed: Arc<[String]>,
+    error: Option<Arc<AppConfigError<T>>>,
+}
+
+impl<T> Reload<T> {
+    /// Get the current configuration.
+    ///
+    /// If the reload failed, this is the last good configuration.
+    pub fn config(&self) -> &Arc<T> {
+        &self.config
+    }
+
+    /// Get the top-level keys whose values changed.
+    pub fn changed(&self) -> &[String] {
+        &self.changed
+    }
+
+    /// Get the error the reload failed with, if it failed.
+    pub fn error(&self) -> Option<&AppConfigError<T>> {
+        self.error.as_deref()
+    }
+}
+
+impl<T> Clone for Reload<T> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            changed: self.changed.clone(),
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// The state shared by a reloader and its worker.
+struct Shared<T> {
+    loader: Loader<T>,
+    values: Mutex<Dict>,
+    tx: watch::Sender<Reload<T>>,
+}
+
+impl<T: AppConfig> Shared<T> {
+    /// Reload the configuration and publish the outcome.
+    fn reload(&self) -> Reload<T> {
+        let mut values = lock(&self.values);
+        let reload = match self.loader.load_values() {
+            Ok((config, new_values)) => {
+                let changed = changed_keys(&values, &new_values);
+                *values = new_values;
+                let failed = self.tx.borrow().error.is_some();
+                if changed.is_empty() && !failed {
+                    // nothing to tell the subscribers about
+                    return self.tx.borrow().clone();
+                }
+                Reload {
+                    config: Arc::new(config),
+                    changed: changed.into(),
+                    error: None,
+                }
+            }
+            Err(e) => Reload {
+                config: self.tx.borrow().config.clone(),
+                changed: Vec::new().into(),
+                error: Some(Arc::new(e)),
+            },
+        };
+        self.tx.send_replace(reload.clone());
+        reload
+    }
+}
+
+/// Holds the current configuration and reloads it.
+///
+/// The reloader can be cloned; its worker stops once every clone has been
+/// dropped.
+pub struct Reloader<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: AppConfig + Send + Sync + 'static> Reloader<T> {
+    /// Load the configuration and create a reloader for it.
+    ///
+    /// Reloading on `SIGHUP` requires this to be called from a tokio runtime.
+    pub(crate) fn new(
+        loader: Loader<T>,
+        options: ReloadOptions,
+    ) -> Result<(Self, ReloadWorker), T> {
+        let (config, values) = loader.load_values()?;
+        let (tx, _) = watch::channel(Reload {
+            config: Arc::new(config),
+            changed: Vec::new().into(),
+            error: None,
+        });
+        let hangup = hangup(options.sighup)?;
+        let fingerprint = (fingerprint(&loader.watched_paths()), loader.revision());
+
+        let shared = Arc::new(Shared {
+            loader,
+            values: Mutex::new(values),
+            tx,
+        });
+        let worker = watch_sources(Arc::downgrade(&shared), options, hangup, fingerprint);
+
+        Ok((Self { shared }, ReloadWorker(Box::pin(worker))))
+    }
+
+    /// Subscribe to reloads.
+    pub fn subscribe(&self) -> watch::Receiver<Reload<T>> {
+        self.shared.tx.subscribe()
+    }
+
+    /// Get the current configuration.
+    pub fn current(&self) -> Arc<T> {
+        self.shared.tx.borrow().config.clone()
+    }
+
+    /// Reload the configuration now.
+    ///
+    /// Subscribers are only notified if a value changed, if the reload
+    /// failed, or if it succeeded after a failed one.
+    ///
+    /// This blocks while the sources are read.
+    pub fn reload(&self) -> Reload<T> {
+        self.shared.reload()
+    }
+}
+
+impl<T> Clone for Reloader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// A future that reloads a configuration when its sources change.
+///
+/// The worker completes once every [`Reloader`] has been dropped.
+pub struct ReloadWorker(Pin<Box<dyn Future<Output = ()> + Send>>);
+
+impl Future for ReloadWorker {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+/// Check the sources every interval and reload when they changed or when
+/// the process is hung up.
+///
+/// The sources are checked and reloaded on the blocking thread pool of the
+/// tokio runtime, since both read files.
+async fn watch_sources<T: AppConfig + Send + Sync + 'static>(
+    shared: Weak<Shared<T>>,
+    options: ReloadOptions,
+    mut hangup: Option<Hangup>,
+    mut last: Fingerprint,
+) {
+    let mut interval = tokio::time::interval(options.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        let hung_up = tokio::select! {
+            _ = interval.tick() => false,
+            _ = hung_up(&mut hangup) => true,
+        };
+
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        let check = tokio::task::spawn_blocking(move || {
+            let current = (
+                fingerprint(&shared.loader.watched_paths()),
+                shared.loader.revision(),
+            );
+            if hung_up || current != last {
+                shared.reload();
+            }
+            current
+        });
+        last = match check.await {
+            Ok(current) => current,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            // the runtime is shutting down
+            Err(_) => return,
+        };
+    }
+}
+
+/// The modification times and sizes of the watched files, and the revision
+/// of the remotes.
+type Fingerprint = (Files, u64);
+
+/// The modification times and sizes of files.
+type Files = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+/// Get the modification times and sizes of `paths`.
+///
+/// Symbolic links are followed, so swapping the target of a link changes the
+/// fingerprint.
+fn fingerprint(paths: &[PathBuf]) -> Files {
+    paths
+        .iter()
+        .map(|path| {
+            let stamp = fs::metadata(path)
+                .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+                .ok();
+            (path.clone(), stamp)
+        })
+        .collect()
+}
+
+/// Get the top-level keys whose values differ between `old` and `new`.
+fn changed_keys(old: &Dict, new: &Dict) -> Vec<String> {
+    let mut changed: Vec<String> = old
+        .iter()
+        .filter(|(key, value)| new.get(*key) != Some(value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    changed.extend(new.keys().filter(|key| !old.contains_key(*key)).cloned());
+    changed.sort();
+    changed
+}
+
+#[cfg(unix)]
+type Hangup = tokio::signal::unix::Signal;
+#[cfg(not(unix))]
+type Hangup = std::convert::Infallible;
+
+/// Listen for `SIGHUP`, if `enabled`.
+#[cfg(unix)]
+fn hangup<T>(enabled: bool) -> Result<Option<Hangup>, T> {
+    use tokio::signal::unix::signal;
+    use tokio::signal::unix::SignalKind;
+
+    if !enabled {
+        return Ok(None);
+    }
+    signal(SignalKind::hangup()).map(Some).map_err(|e| {
+        AppConfigError::new(figment::Error::from(format!(
+            "failed to listen for SIGHUP: {}",
+            e
+        )))
+    })
+}
+
+/// Listen for `SIGHUP`, if `enabled`.
+#[cfg(not(unix))]
+fn hangup<T>(_enabled: bool) -> Result<Option<Hangup>, T> {
+    Ok(None)
+}
+
+/// Wait until the process is hung up.
+async fn hung_up(hangup: &mut Option<Hangup>) {
+    match hangup {
+        #[cfg(unix)]
+        Some(signal) => {
+            signal.recv().await;
+        }
+        #[cfg(not(unix))]
+        Some(never) => match *never {},
+        None => std::future::pending().await,
+    }
+}
+
+/// Lock the values, ignoring poisoning.
+fn lock(values: &Mutex<Dict>) -> MutexGuard<'_, Dict> {
+    values.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::ReloadOptions;
+    use crate::AppConfig;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        port: u16,
+        host: String,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+    }
+
+    /// Run `f` on a tokio runtime.
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    #[test]
+    fn test_reload_file_changed() {
+        Jail::expect_with(|jail| {
+            jail.create_file("config.toml", "port = 1\nhost = 'a'")?;
+
+            block_on(async {
+                let options = ReloadOptions::new().interval(Duration::from_millis(10));
+                let (reloader, worker) = TestConfig::loader().reloading(options).unwrap();
+                let mut rx = reloader.subscribe();
+                tokio::spawn(worker);
+                assert_eq!(reloader.current().port, 1);
+
+                jail.create_file("config.toml", "port = 22\nhost = 'a'")
+                    .unwrap();
+                tokio::time::timeout(Duration::from_secs(5), rx.changed())
+                    .await
+                    .unwrap()
+                    .unwrap();
+
+                let reload = rx.borrow().clone();
+                assert!(reload.error().is_none());
+                assert_eq!(reload.config().port, 22);
+                assert_eq!(reload.changed(), ["port"]);
+            });
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_reload_failed_keeps_last_good() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_PORT", 1);
+            jail.set_env("APP_HOST", "a");
+
+            block_on(async {
+                let (reloader, _worker) = TestConfig::loader()
+                    .reloading(ReloadOptions::new())
+                    .unwrap();
+                let rx = reloader.subscribe();
+
+                jail.set_env("APP_PORT", "invalid");
+                let reload = reloader.reload();
+                assert!(reload.error().is_some());
+                assert_eq!(reload.config().port, 1);
+                assert!(rx.has_changed().unwrap());
+
+                jail.set_env("APP_PORT", 2);
+                jail.set_env("APP_HOST", "b");
+                let reload = reloader.reload();
+                assert!(reload.error().is_none());
+                assert_eq!(reload.changed(), ["host", "port"]);
+                assert_eq!(reloader.current().host, "b");
+            });
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_reload_recovered_with_same_values() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_PORT", 1);
+            jail.set_env("APP_HOST", "a");
+
+            block_on(async {
+                let (reloader, _worker) = TestConfig::loader()
+                    .reloading(ReloadOptions::new())
+                    .unwrap();
+                let mut rx = reloader.subscribe();
+
+                jail.set_env("APP_PORT", "invalid");
+                assert!(reloader.reload().error().is_some());
+                rx.borrow_and_update();
+
+                jail.set_env("APP_PORT", 1);
+                let reload = reloader.reload();
+                assert!(reload.error().is_none());
+                assert!(reload.changed().is_empty());
+                assert!(rx.has_changed().unwrap());
+                assert!(rx.borrow_and_update().error().is_none());
+
+                let _ = reloader.reload();
+                assert!(!rx.has_changed().unwrap());
+            });
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_reload_worker_stops() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_PORT", 1);
+            jail.set_env("APP_HOST", "a");
+
+            block_on(async {
+                let options = ReloadOptions::new().interval(Duration::from_millis(10));
+                let (reloader, worker) = TestConfig::loader().reloading(options).unwrap();
+                let worker = tokio::spawn(worker);
+
+                drop(reloader);
+                tokio::time::timeout(Duration::from_secs(5), worker)
+                    .await
+                    .unwrap()
+                    .unwrap();
+            });
+            Ok(())
+        });
+    }
+}
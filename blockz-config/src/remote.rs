@@ -0,0 +1,524 @@
+//! Values of app configurations fetched from remote services, such as
+//! key-value stores.
+//!
+//! A [`Remote`] holds the last values fetched by a [`RemoteProvider`] and is
+//! merged into the layer stack with [`Loader::remote`](crate::Loader::remote).
+//! The values are fetched with [`Remote::refresh`], on load with
+//! [`Loader::load_async`](crate::Loader::load_async) and periodically by a
+//! [`RemoteWorker`]. When the remote is unavailable, the last values are kept,
+//! and are read back from the cache file of the remote if it has one.
+//!
+//! [`Directory`] and [`HttpJson`] stand in for real services in tests and
+//! development.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use figment::value::Dict;
+use figment::value::Map;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+use figment::Source;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::MissedTickBehavior;
+use url::Position;
+use url::Url;
+
+use crate::tree;
+use crate::Layer;
+
+/// A future that fetches the values of a remote.
+pub type Fetch<'a> = Pin<Box<dyn Future<Output = io::Result<Dict>> + Send + 'a>>;
+
+/// A remote service that holds values of a configuration.
+pub trait RemoteProvider: Send + Sync {
+    /// Get the name of the remote, such as its URL.
+    ///
+    /// This is reported as the origin of the values of the remote.
+    fn name(&self) -> String;
+
+    /// Fetch every value from the remote.
+    fn fetch(&self) -> Fetch<'_>;
+}
+
+/// The last values fetched from a [`RemoteProvider`].
+///
+/// A remote can be cloned; the clones share their values.
+#[derive(Clone)]
+pub struct Remote {
+    provider: Arc<dyn RemoteProvider>,
+    cache: Option<PathBuf>,
+    state: Arc<Mutex<State>>,
+}
+
+/// The values of a remote.
+#[derive(Default)]
+struct State {
+    values: Option<Dict>,
+    stale: bool,
+    revision: u64,
+}
+
+impl Remote {
+    /// Create a new `Remote`, without values until it is refreshed.
+    pub fn new<P: RemoteProvider + 'static>(provider: P) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            cache: None,
+            state: Arc::default(),
+        }
+    }
+
+    /// Keep a snapshot of the values in a file, to fall back to when the
+    /// remote is unavailable.
+    ///
+    /// The file holds every value of the remote as plain JSON, including
+    /// credentials; on unix, it is only readable by its owner.
+    pub fn cache<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cache = Some(path.into());
+        self
+    }
+
+    /// Fetch the values from the remote.
+    ///
+    /// If the remote is unavailable, the last values are kept, or read from
+    /// the cache file if there are none yet; this fails only if there are no
+    /// values to fall back to. The cache file is written and read on the
+    /// blocking thread pool, so this must run on a tokio runtime.
+    pub async fn refresh(&self) -> io::Result<()> {
+        refresh(&*self.provider, self.cache.as_deref(), &self.state).await
+    }
+
+    /// Refresh the values every `interval`.
+    ///
+    /// The worker completes once every clone of the remote has been dropped.
+    pub fn refreshing(&self, interval: Duration) -> RemoteWorker {
+        let provider = self.provider.clone();
+        let cache = self.cache.clone();
+        let state = Arc::downgrade(&self.state);
+        RemoteWorker(Box::pin(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let state = match state.upgrade() {
+                    Some(state) => state,
+                    None => return,
+                };
+                // failures are retried on the next tick
+                let _ = refresh(&*provider, cache.as_deref(), &state).await;
+            }
+        }))
+    }
+
+    /// Returns whether the values were not fetched by the last refresh,
+    /// because the remote was unavailable.
+    pub fn is_stale(&self) -> bool {
+        lock(&self.state).stale
+    }
+
+    /// Get the name of the remote.
+    pub(crate) fn name(&self) -> String {
+        self.provider.name()
+    }
+
+    /// Get a number that changes whenever the values change.
+    pub fn revision(&self) -> u64 {
+        lock(&self.state).revision
+    }
+}
+
+impl Provider for Remote {
+    fn metadata(&self) -> Metadata {
+        Metadata::from(Layer::Remote.name(), Source::Custom(self.provider.name()))
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        let values = lock(&self.state).values.clone().unwrap_or_default();
+        Ok(Profile::Global.collect(values))
+    }
+}
+
+/// A future that refreshes the values of a remote periodically.
+///
+/// See [`Remote::refreshing`].
+pub struct RemoteWorker(Pin<Box<dyn Future<Output = ()> + Send>>);
+
+impl Future for RemoteWorker {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+/// Fetch the values of a remote into `state`.
+async fn refresh(
+    provider: &dyn RemoteProvider,
+    cache: Option<&Path>,
+    state: &Mutex<State>,
+) -> io::Result<()> {
+    match provider.fetch().await {
+        Ok(values) => {
+            if let Some(cache) = cache {
+                let (cache, cached) = (cache.to_path_buf(), values.clone());
+                // the cache is a best effort
+                let _ = blocking(move || write_cache(&cache, &cached)).await;
+            }
+            let mut state = lock(state);
+            if state.values.as_ref() != Some(&values) {
+                state.values = Some(values);
+                state.revision += 1;
+            }
+            state.stale = false;
+            Ok(())
+        }
+        Err(e) => {
+            let cached = match cache {
+                Some(cache) if lock(state).values.is_none() => {
+                    let cache = cache.to_path_buf();
+                    blocking(move || read_cache(&cache)).await.ok()
+                }
+                _ => None,
+            };
+            let mut state = lock(state);
+            state.stale = true;
+            if state.values.is_none() {
+                state.values = cached;
+                if state.values.is_none() {
+                    return Err(e);
+                }
+                state.revision += 1;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Write the values of a remote to its cache file.
+fn write_cache(path: &Path, values: &Dict) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(values)?;
+    // replace the file at once, so that it is never read half-written
+    let tmp = path.with_extension("tmp");
+    // a new file, so that its permissions are ours and no link is followed
+    let _ = fs::remove_file(&tmp);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(&tmp)?.write_all(&json)?;
+    fs::rename(&tmp, path)
+}
+
+/// Read the values of a remote from its cache file.
+fn read_cache(path: &Path) -> io::Result<Dict> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// Run blocking file operations on the blocking thread pool of the tokio
+/// runtime.
+async fn blocking<R, F>(f: F) -> io::Result<R>
+where
+    R: Send + 'static,
+    F: FnOnce() -> io::Result<R> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// Lock the state of a remote, ignoring poisoning.
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A remote that is a directory with a file per key.
+///
+/// The directory is read as a [`DirectoryTree`](crate::DirectoryTree).
+pub struct Directory {
+    path: PathBuf,
+}
+
+impl Directory {
+    /// Create a new `Directory` remote.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl RemoteProvider for Directory {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    /// The directory is read on the blocking thread pool of the tokio
+    /// runtime.
+    fn fetch(&self) -> Fetch<'_> {
+        let path = self.path.clone();
+        Box::pin(blocking(move || tree::read(&path)))
+    }
+}
+
+/// A remote that serves a JSON object over plain HTTP, such as a local
+/// development server.
+pub struct HttpJson {
+    url: String,
+    timeout: Duration,
+}
+
+impl HttpJson {
+    /// Create a new `HttpJson` remote for an `http://` URL, with a timeout of
+    /// five seconds.
+    pub fn new<U: Into<String>>(url: U) -> Self {
+        Self {
+            url: url.into(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Set how long a fetch may take.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get the body of the response to a `GET` request.
+    async fn get(&self) -> io::Result<Vec<u8>> {
+        let url =
+            Url::parse(&self.url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let host = match url.host_str() {
+            Some(host) if url.scheme() == "http" => host,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{}` is not an `http://` URL", self.url),
+                ))
+            }
+        };
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let mut stream = TcpStream::connect((host, port)).await?;
+        // HTTP/1.0 responses are not chunked and end with the connection
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+            &url[Position::BeforePath..Position::AfterQuery],
+            host
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid response from `{}`", self.url),
+            )
+        };
+        let end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(invalid)?;
+        let head = std::str::from_utf8(&response[..end]).map_err(|_| invalid())?;
+        let status: u16 = head
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(invalid)?;
+        if !(200..300).contains(&status) {
+            return Err(io::Error::other(format!(
+                "`{}` responded with status {}",
+                self.url, status
+            )));
+        }
+        Ok(response.split_off(end + 4))
+    }
+}
+
+impl RemoteProvider for HttpJson {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    fn fetch(&self) -> Fetch<'_> {
+        Box::pin(async move {
+            let body = tokio::time::timeout(self.timeout, self.get())
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, format!("`{}` timed out", self.url))
+                })??;
+            Ok(serde_json::from_slice(&body)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use figment::Jail;
+    use serde::Deserialize;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    use super::Directory;
+    use super::HttpJson;
+    use super::Remote;
+    use crate::AppConfig;
+    use crate::Layer;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        port: u16,
+        database: Database,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Database {
+        url: String,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+    }
+
+    /// Run `f` on a tokio runtime.
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    /// Serve `body` as JSON to every request, or `404` if it is empty.
+    async fn serve(body: Arc<Mutex<String>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/config?env=test", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await.unwrap();
+                let body = body.lock().unwrap().clone();
+                let response = if body.is_empty() {
+                    "HTTP/1.0 404 Not Found\r\n\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+                        body
+                    )
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_remote_directory() {
+        Jail::expect_with(|jail| {
+            jail.create_dir("kv/database")?;
+            jail.create_file("kv/port", "8080\n")?;
+            jail.create_file("kv/database/url", "postgres://db")?;
+            jail.create_file("kv/.hidden", "x")?;
+            jail.set_env("APP_PORT", 80);
+
+            block_on(async {
+                let remote = Remote::new(Directory::new("kv")).cache("kv.json");
+                let loader = TestConfig::loader().remote(remote.clone());
+                let config = loader.load_async().await.unwrap();
+                assert_eq!(config.port, 80);
+                assert_eq!(config.database.url, "postgres://db");
+                let explanation = loader.explain().unwrap();
+                let url = explanation.get("database.url").unwrap();
+                assert_eq!(url.layer(), Some(Layer::Remote));
+                assert_eq!(url.origin(), Some("kv"));
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let mode = std::fs::metadata("kv.json").unwrap().permissions().mode();
+                    assert_eq!(mode & 0o777, 0o600);
+                }
+
+                // the remote is unavailable: the last values are kept
+                std::fs::remove_dir_all("kv").unwrap();
+                remote.refresh().await.unwrap();
+                assert!(remote.is_stale());
+                assert_eq!(loader.load().unwrap().database.url, "postgres://db");
+
+                // a new process falls back to the cache
+                let cached = Remote::new(Directory::new("kv")).cache("kv.json");
+                let config = TestConfig::loader()
+                    .remote(cached)
+                    .load_async()
+                    .await
+                    .unwrap();
+                assert_eq!(config.database.url, "postgres://db");
+
+                let err = TestConfig::loader()
+                    .remote(Remote::new(Directory::new("kv")))
+                    .load_async()
+                    .await
+                    .unwrap_err();
+                assert!(err.to_string().contains("failed to fetch `kv`"));
+            });
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_remote_http_json() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_DATABASE__URL", "postgres://db");
+
+            block_on(async {
+                let body = Arc::new(Mutex::new(r#"{"port": 1}"#.to_string()));
+                let url = serve(body.clone()).await;
+                let remote = Remote::new(HttpJson::new(url));
+                let loader = TestConfig::loader().remote(remote.clone());
+                assert_eq!(loader.load_async().await.unwrap().port, 1);
+
+                tokio::spawn(remote.refreshing(Duration::from_millis(10)));
+                *body.lock().unwrap() = r#"{"port": 2}"#.to_string();
+                tokio::time::timeout(Duration::from_secs(5), async {
+                    while loader.load().unwrap().port != 2 {
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                })
+                .await
+                .unwrap();
+
+                body.lock().unwrap().clear();
+                remote.refresh().await.unwrap();
+                assert!(remote.is_stale());
+                let err = Remote::new(HttpJson::new(remote.name()))
+                    .refresh()
+                    .await
+                    .unwrap_err();
+                assert!(err.to_string().contains("responded with status 404"));
+            });
+            Ok(())
+        });
+    }
+}
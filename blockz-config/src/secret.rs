@@ -0,0 +1,356 @@
+//! Secret values and the files that hold them.
+
+use std::env;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+use figment::value::Dict;
+use figment::value::Map;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use zeroize::Zeroize;
+
+use crate::env::env_var;
+use crate::env::prefixed;
+use crate::field;
+use crate::AppConfig;
+
+/// The suffix of the environment variables that name the file holding the
+/// value of a key.
+pub const FILE_SUFFIX: &str = "_FILE";
+
+/// A value that is redacted when it is formatted and wiped from memory when
+/// it is dropped.
+///
+/// The value is serialized as is, so that it can be given a default.
+///
+/// Only the value held by the `Secret` is wiped: the copies made while the
+/// configuration is loaded, such as the contents of environment variables and
+/// of the files named by `_FILE` variables, are dropped without being wiped.
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    /// Create a new `Secret`.
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Get the secret value.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Zeroize + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Zeroize + Default> Default for Secret<T> {
+    fn default() -> Self {
+        Self(T::default())
+    }
+}
+
+impl<T: Zeroize> Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Zeroize> Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<'de, T: Zeroize + Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+impl<T: Zeroize + Serialize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// A key whose value is read from a file.
+#[derive(Clone, Debug)]
+struct SecretFile {
+    /// The key path.
+    path: String,
+    /// The variable that names the file.
+    var: String,
+    /// The variable that sets the key directly.
+    direct: String,
+    /// The file that holds the value.
+    file: PathBuf,
+    /// Whether the key is the path of a declared field.
+    declared: bool,
+}
+
+/// A provider for the keys whose values are read from files.
+///
+/// For every key, a `<KEY>_FILE` environment variable names the file that
+/// holds its value, with the trailing newlines trimmed. This holds both for
+/// the variables under the package prefix and for the variables given to
+/// fields with `#[env]`.
+///
+/// A variable whose key is not declared as a field may also set a field whose
+/// name ends in `_file`, so it is kept as is as well, and it is skipped if the
+/// key is set directly or if the file cannot be read.
+#[derive(Clone)]
+pub(crate) struct SecretFiles {
+    files: Vec<SecretFile>,
+}
+
+impl SecretFiles {
+    /// Find the files named by the environment variables of `T`.
+    pub(crate) fn new<T: AppConfig>() -> Self {
+        let fields = T::fields();
+        let paths = field::paths(&fields);
+
+        let mut files: Vec<SecretFile> = prefixed::<T>()
+            .iter()
+            .filter_map(|(key, file)| {
+                let path = file_key(key.as_str(), &paths)?;
+                let direct = env_var::<T>(path);
+                Some(SecretFile {
+                    path: path.to_string(),
+                    var: format!("{}{}", direct, FILE_SUFFIX),
+                    direct,
+                    file: file.into(),
+                    declared: paths.iter().any(|other| other == path),
+                })
+            })
+            .collect();
+
+        for (var, path) in field::env_vars(&fields) {
+            let file_var = format!("{}{}", var, FILE_SUFFIX);
+            if let Some(file) = env::var_os(&file_var) {
+                files.push(SecretFile {
+                    path,
+                    var: file_var,
+                    direct: var.to_string(),
+                    file: file.into(),
+                    declared: true,
+                });
+            }
+        }
+
+        Self { files }
+    }
+
+    /// Returns whether there are no files.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Get the paths of the files of declared fields.
+    ///
+    /// The other files may be anything, such as logs that change all the
+    /// time.
+    #[cfg(feature = "reload")]
+    pub(crate) fn paths(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|file| file.declared)
+            .map(|file| file.file.clone())
+            .collect()
+    }
+
+    /// Get the variable that names the file of a key path.
+    pub(crate) fn var(&self, path: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|file| file.path == path)
+            .map(|file| file.var.as_str())
+    }
+}
+
+impl Provider for SecretFiles {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("secret files")
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        let mut dict = Dict::new();
+        for file in &self.files {
+            let direct = env::var_os(&file.direct).is_some();
+            if direct && !file.declared {
+                continue;
+            }
+            if direct {
+                return Err(figment::Error::from(format!(
+                    "both `{}` and `{}` are set",
+                    file.direct, file.var
+                )));
+            }
+
+            let mut value = match fs::read_to_string(&file.file) {
+                Ok(value) => value,
+                Err(_) if !file.declared => continue,
+                Err(e) => {
+                    return Err(figment::Error::from(format!(
+                        "failed to read {} from `{}`: {}",
+                        file.file.display(),
+                        file.var,
+                        e
+                    )))
+                }
+            };
+            value.truncate(value.trim_end_matches(&['\n', '\r'][..]).len());
+            field::insert(&mut dict, &file.path, value.into());
+        }
+        Ok(Profile::Global.collect(dict))
+    }
+}
+
+/// Get the key path whose value is read from a file named by the environment
+/// key `key`, if it names one.
+///
+/// Keys of declared fields that end in `_file` are not treated as file names.
+pub(crate) fn file_key<'a>(key: &'a str, paths: &[String]) -> Option<&'a str> {
+    let suffix = FILE_SUFFIX.to_ascii_lowercase();
+    key.strip_suffix(suffix.as_str())
+        .filter(|path| !path.is_empty() && !paths.iter().any(|other| other == key))
+}
+
+/// Get the key path of a declared field whose value is read from a file named
+/// by the environment key `key`, if it names one.
+///
+/// Unlike the other `_FILE` variables, these do not set their own key.
+pub(crate) fn declared_file_key<'a>(key: &'a str, paths: &[String]) -> Option<&'a str> {
+    file_key(key, paths).filter(|path| paths.iter().any(|other| other == path))
+}
+
+#[cfg(test)]
+mod test {
+    use figment::value::Value;
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::Secret;
+    use crate::AppConfig;
+    use crate::Field;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        password: Secret<String>,
+        log_file: Option<String>,
+        token: Option<Secret<String>>,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("password", "Secret<String>"),
+                Field::new("log_file", "Option<String>"),
+                Field::new("token", "Option<Secret<String>>").with_env("TEST_TOKEN"),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_secret_redacted() {
+        let secret = Secret::new("hunter2".to_string());
+        assert_eq!(format!("{:?}", secret), "[REDACTED]");
+        assert_eq!(secret.to_string(), "[REDACTED]");
+        assert_eq!(secret.expose(), "hunter2");
+
+        let value: Secret<String> = Value::from("hunter2").deserialize().unwrap();
+        assert_eq!(value.expose(), "hunter2");
+    }
+
+    #[test]
+    fn test_secret_files() {
+        Jail::expect_with(|jail| {
+            jail.create_file("password", "hunter2\r\n\n")?;
+            jail.create_file("token", "abc\n")?;
+            jail.set_env("APP_PASSWORD_FILE", "password");
+            jail.set_env("APP_LOG_FILE", "app.log");
+            jail.set_env("TEST_TOKEN_FILE", "token");
+
+            let config = TestConfig::load().unwrap();
+            assert_eq!(config.password.expose(), "hunter2");
+            assert_eq!(config.log_file.as_deref(), Some("app.log"));
+            assert_eq!(config.token.unwrap().expose(), "abc");
+            assert!(!format!("{:?}", TestConfig::load().unwrap()).contains("hunter2"));
+
+            jail.set_env("APP_PASSWORD", "direct");
+            let err = TestConfig::load().unwrap_err();
+            assert!(err.to_string().contains("both `APP_PASSWORD` and"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_undeclared_file_key() {
+        #[derive(Debug, Deserialize)]
+        struct LogConfig {
+            log_file: String,
+            password: String,
+        }
+
+        impl AppConfig for LogConfig {
+            const PACKAGE: &'static str = "APP_";
+        }
+
+        Jail::expect_with(|jail| {
+            jail.create_file("app.log", "a log line")?;
+            jail.create_file("password", "hunter2\n")?;
+            jail.set_env("APP_LOG_FILE", "app.log");
+            jail.set_env("APP_PASSWORD_FILE", "password");
+
+            let config = LogConfig::load().unwrap();
+            assert_eq!(config.log_file, "app.log");
+            assert_eq!(config.password, "hunter2");
+
+            // the log file need not exist, and a key set directly wins
+            jail.set_env("APP_LOG_FILE", "missing.log");
+            jail.set_env("APP_PASSWORD", "direct");
+            let config = LogConfig::load().unwrap();
+            assert_eq!(config.log_file, "missing.log");
+            assert_eq!(config.password, "direct");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_secret_file_unreadable() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_PASSWORD_FILE", "missing");
+
+            let err = TestConfig::load().unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("failed to read missing from `APP_PASSWORD_FILE`"));
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,64 @@
+//! Suggestions for misspelled names.
+
+/// Get the candidate closest to `name`, if one is close enough to be a
+/// likely misspelling.
+pub(crate) fn closest<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Get the edit distance between `a` and `b`, where swapping two adjacent
+/// characters counts as a single edit.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::closest;
+    use super::distance;
+
+    #[test]
+    fn test_suggest_closest() {
+        assert_eq!(distance("port", "prot"), 1);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("database.url", "database.uri"), 1);
+
+        let candidates = ["port", "host", "database.url"];
+        assert_eq!(closest("prot", candidates.iter().copied()), Some("port"));
+        assert_eq!(
+            closest("databse.url", candidates.iter().copied()),
+            Some("database.url")
+        );
+        assert_eq!(closest("workers", candidates.iter().copied()), None);
+    }
+}
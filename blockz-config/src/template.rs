@@ -0,0 +1,433 @@
+//! Templates generated from the fields of app configurations.
+//!
+//! A service can print these to document the configuration it reads, e.g.
+//! behind a `--print-config-template` flag:
+//!
+//! ```
+//! # #[macro_use] extern crate blockz_config;
+//! use blockz_config::template;
+//!
+//! config!("APP_" {
+//!     /// The port to listen on.
+//!     port: u16 = 8080,
+//! });
+//!
+//! # fn main() {
+//! if std::env::args().any(|arg| arg == "--print-config-template") {
+//!     println!("{}", template::env_example::<Config>());
+//! }
+//! # }
+//! ```
+
+use std::fmt::Write;
+use std::ops::Bound;
+
+use figment::value::Value;
+use serde_json::json;
+use serde_json::Map;
+
+use crate::env;
+use crate::field::Literal;
+use crate::field::Type;
+use crate::secret::FILE_SUFFIX;
+use crate::AppConfig;
+use crate::Constraint;
+use crate::Field;
+
+/// Get the JSON Schema of the configuration, as pretty-printed JSON.
+///
+/// The schema holds the types, docs, defaults and constraints of the fields.
+pub fn json_schema<T: AppConfig>() -> String {
+    let mut schema = object_schema(&T::fields());
+    schema.insert(
+        "$schema".to_string(),
+        json!("https://json-schema.org/draft/2020-12/schema"),
+    );
+    schema.insert("title".to_string(), json!(T::PACKAGE));
+    serde_json::to_string_pretty(&schema).expect("a schema is always serializable")
+}
+
+/// Get a `.env.example` file with every environment variable of the
+/// configuration, commented with the docs and constraints of its field.
+///
+/// Variables are set to the default value of their field, if it is not a
+/// secret, or left empty.
+pub fn env_example<T: AppConfig>() -> String {
+    fn walk<T: AppConfig>(fields: &[Field], prefix: &str, out: &mut String) {
+        for field in fields {
+            let path = format!("{}{}", prefix, field.name);
+            if field.is_section() {
+                walk::<T>(&field.fields, &format!("{}.", path), out);
+                continue;
+            }
+
+            let var = env::env_var::<T>(&path);
+            for line in describe(field) {
+                let _ = writeln!(out, "# {}", line);
+            }
+            if let Some(env) = field.env {
+                let _ = writeln!(out, "# Also set by {}.", env);
+            }
+            if field.is_secret() {
+                let _ = writeln!(
+                    out,
+                    "# Or set {}{} to a file that holds the value.",
+                    var, FILE_SUFFIX
+                );
+            }
+            let value = match &field.default {
+                Some(default) if !field.is_secret() => env_value(default),
+                _ => String::new(),
+            };
+            let _ = writeln!(out, "{}={}\n", var, value);
+        }
+    }
+
+    let mut out = String::new();
+    walk::<T>(&T::fields(), "", &mut out);
+    out.truncate(out.trim_end().len() + 1);
+    out
+}
+
+/// Get a sample TOML config file, with every field commented with its docs
+/// and constraints.
+///
+/// Fields are set to their default values; fields without one, and secrets,
+/// are commented out.
+pub fn sample_toml<T: AppConfig>() -> String {
+    fn table(fields: &[Field], header: Option<&str>, out: &mut String) {
+        if let Some(header) = header {
+            let _ = writeln!(out, "[{}]", header);
+        }
+        for field in fields.iter().filter(|field| !field.is_section()) {
+            for line in describe(field) {
+                let _ = writeln!(out, "# {}", line);
+            }
+            match &field.default {
+                Some(default) if !field.is_secret() => {
+                    let _ = writeln!(out, "{} = {}\n", field.name, Literal(default));
+                }
+                _ => {
+                    let _ = writeln!(out, "# {} = {}\n", field.name, placeholder(field.ty));
+                }
+            }
+        }
+
+        for section in fields.iter().filter(|field| field.is_section()) {
+            let header = match header {
+                Some(header) => format!("{}.{}", header, section.name),
+                None => section.name.to_string(),
+            };
+            for line in section.doc().lines() {
+                let _ = writeln!(out, "# {}", line);
+            }
+            table(&section.fields, Some(&header), out);
+        }
+    }
+
+    let mut out = String::new();
+    table(&T::fields(), None, &mut out);
+    out.truncate(out.trim_end().len() + 1);
+    out
+}
+
+/// Get the lines that describe a field: its docs, its constraints and
+/// whether it is required.
+fn describe(field: &Field) -> Vec<String> {
+    let mut lines: Vec<String> = field.doc().lines().map(str::to_string).collect();
+    for constraint in &field.constraints {
+        if let Constraint::Custom(_) = constraint {
+            continue;
+        }
+        let constraint = constraint.to_string();
+        let mut chars = constraint.chars();
+        if let Some(first) = chars.next() {
+            lines.push(format!("{}{}.", first.to_ascii_uppercase(), chars.as_str()));
+        }
+    }
+    if field.is_required() {
+        lines.push("Required.".to_string());
+    }
+    lines
+}
+
+/// Get a value as it is written in an environment variable.
+fn env_value(value: &Value) -> String {
+    match value {
+        Value::String(_, s) if !s.contains(char::is_whitespace) && !s.contains('#') => s.clone(),
+        value => Literal(value).to_string(),
+    }
+}
+
+/// Get a placeholder value for a type.
+fn placeholder(ty: &str) -> &'static str {
+    match schema_type(ty).get("type").and_then(|ty| ty.as_str()) {
+        Some("boolean") => "false",
+        Some("integer") | Some("number") => "0",
+        Some("array") => "[]",
+        Some("object") => "{}",
+        _ => "\"\"",
+    }
+}
+
+/// Get the schema of a struct with `fields`.
+fn object_schema(fields: &[Field]) -> Map<String, serde_json::Value> {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for field in fields {
+        let mut schema = if field.is_section() {
+            object_schema(&field.fields)
+        } else {
+            field_schema(field)
+        };
+        let doc = field.doc();
+        if !doc.is_empty() {
+            schema.insert("description".to_string(), json!(doc));
+        }
+        properties.insert(field.name.to_string(), schema.into());
+        if field.is_required() {
+            required.push(field.name);
+        }
+    }
+
+    let mut schema = Map::new();
+    schema.insert("type".to_string(), json!("object"));
+    schema.insert("properties".to_string(), properties.into());
+    if !required.is_empty() {
+        schema.insert("required".to_string(), json!(required));
+    }
+    schema
+}
+
+/// Get the schema of a field that is not a section.
+fn field_schema(field: &Field) -> Map<String, serde_json::Value> {
+    let mut schema = schema_type(field.ty);
+    if field.is_secret() {
+        schema.insert("writeOnly".to_string(), json!(true));
+    }
+    if let Some(default) = field.default.as_ref().filter(|_| !field.is_secret()) {
+        let default = serde_json::to_value(default).expect("a value is always serializable");
+        schema.insert("default".to_string(), default);
+    }
+
+    for constraint in &field.constraints {
+        let is_array = schema.get("type") == Some(&json!("array"));
+        match (constraint, is_array) {
+            (Constraint::NonEmpty, true) => {
+                schema.insert("minItems".to_string(), json!(1));
+            }
+            (Constraint::Custom(_), _) => {}
+            (constraint, true) => {
+                if let Some(serde_json::Value::Object(items)) = schema.get_mut("items") {
+                    constrain(items, constraint);
+                }
+            }
+            (constraint, false) => constrain(&mut schema, constraint),
+        }
+    }
+    schema
+}
+
+/// Add a constraint on a value that is not a list to its schema.
+fn constrain(schema: &mut Map<String, serde_json::Value>, constraint: &Constraint) {
+    fn number(n: f64) -> serde_json::Value {
+        if n.fract() == 0.0 && n.abs() < 2f64.powi(53) {
+            json!(n as i64)
+        } else {
+            json!(n)
+        }
+    }
+
+    match constraint {
+        Constraint::Range { start, end } => {
+            match start {
+                Bound::Included(n) => schema.insert("minimum".to_string(), number(*n)),
+                Bound::Excluded(n) => schema.insert("exclusiveMinimum".to_string(), number(*n)),
+                Bound::Unbounded => None,
+            };
+            match end {
+                Bound::Included(n) => schema.insert("maximum".to_string(), number(*n)),
+                Bound::Excluded(n) => schema.insert("exclusiveMaximum".to_string(), number(*n)),
+                Bound::Unbounded => None,
+            };
+        }
+        Constraint::NonEmpty => {
+            schema.insert("minLength".to_string(), json!(1));
+        }
+        Constraint::Regex(pattern) => {
+            schema.insert("pattern".to_string(), json!(pattern));
+        }
+        Constraint::Url(schemes) => {
+            schema.insert("format".to_string(), json!("uri"));
+            if !schemes.is_empty() {
+                let pattern = format!("^({}):", schemes.join("|"));
+                schema.insert("pattern".to_string(), json!(pattern));
+            }
+        }
+        Constraint::OneOf(values) => {
+            schema.insert("enum".to_string(), json!(values));
+        }
+        Constraint::Custom(_) => {}
+    }
+}
+
+/// Get the schema of a Rust type, as declared.
+///
+/// Types that are not known are described by an empty schema, which accepts
+/// any value.
+fn schema_type(ty: &str) -> Map<String, serde_json::Value> {
+    let ty = Type::parse(ty);
+    let schema = match (ty.name, ty.args.as_slice()) {
+        ("bool", _) => json!({ "type": "boolean" }),
+        ("u8", _) | ("u16", _) | ("u32", _) | ("u64", _) | ("u128", _) | ("usize", _) => {
+            json!({ "type": "integer", "minimum": 0 })
+        }
+        ("i8", _) | ("i16", _) | ("i32", _) | ("i64", _) | ("i128", _) | ("isize", _) => {
+            json!({ "type": "integer" })
+        }
+        ("f32", _) | ("f64", _) => json!({ "type": "number" }),
+        ("String", _) | ("str", _) | ("char", _) | ("PathBuf", _) | ("Url", _) => {
+            json!({ "type": "string" })
+        }
+        ("Option", [inner]) | ("Secret", [inner]) | ("Box", [inner]) | ("Arc", [inner]) => {
+            return schema_type(inner)
+        }
+        ("Vec", [item])
+        | ("VecDeque", [item])
+        | ("HashSet", [item])
+        | ("BTreeSet", [item])
+        | ("[]", [item]) => {
+            json!({ "type": "array", "items": schema_type(item) })
+        }
+        ("HashMap", [_, value]) | ("BTreeMap", [_, value]) => {
+            json!({ "type": "object", "additionalProperties": schema_type(value) })
+        }
+        _ => json!({}),
+    };
+    match schema {
+        serde_json::Value::Object(schema) => schema,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use figment::value::Value;
+    use serde::Deserialize;
+
+    use super::env_example;
+    use super::json_schema;
+    use super::sample_toml;
+    use crate::AppConfig;
+    use crate::Constraint;
+    use crate::Field;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {}
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("port", "u16")
+                    .with_docs(&[" The port to listen on."])
+                    .with_default(Value::from(8080))
+                    .with_constraint(Constraint::range(1..=65535)),
+                Field::new("tags", "Vec<String>").with_constraint(Constraint::NonEmpty),
+                Field::new("level", "Option<String>")
+                    .with_constraint(Constraint::OneOf(&["debug", "info"])),
+                Field::new("database", "Database")
+                    .with_docs(&[" The database."])
+                    .with_fields(vec![
+                        Field::new("url", "String")
+                            .with_env("DATABASE_URL")
+                            .with_constraint(Constraint::Url(&["postgres"])),
+                        Field::new("password", "Secret<String>")
+                            .with_default(Value::from("hunter2")),
+                    ]),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_template_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&json_schema::<TestConfig>()).unwrap();
+
+        assert_eq!(schema["title"], "APP_");
+        assert_eq!(schema["required"], serde_json::json!(["tags", "database"]));
+        assert_eq!(
+            schema["properties"]["port"],
+            serde_json::json!({
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 65535,
+                "default": 8080,
+                "description": "The port to listen on.",
+            })
+        );
+        assert_eq!(schema["properties"]["tags"]["minItems"], 1);
+        assert_eq!(
+            schema["properties"]["level"]["enum"],
+            serde_json::json!(["debug", "info"])
+        );
+
+        let database = &schema["properties"]["database"];
+        assert_eq!(database["required"], serde_json::json!(["url"]));
+        assert_eq!(database["properties"]["url"]["pattern"], "^(postgres):");
+        assert_eq!(database["properties"]["password"]["writeOnly"], true);
+        assert!(database["properties"]["password"].get("default").is_none());
+    }
+
+    #[test]
+    fn test_template_env_example() {
+        assert_eq!(
+            env_example::<TestConfig>(),
+            "# The port to listen on.
+# Must be a number >= 1 and <= 65535.
+APP_PORT=8080
+
+# Must not be empty.
+# Required.
+APP_TAGS=
+
+# Must be `debug` or `info`.
+APP_LEVEL=
+
+# Must be a URL with scheme `postgres`.
+# Required.
+# Also set by DATABASE_URL.
+APP_DATABASE__URL=
+
+# Or set APP_DATABASE__PASSWORD_FILE to a file that holds the value.
+APP_DATABASE__PASSWORD=
+"
+        );
+    }
+
+    #[test]
+    fn test_template_sample_toml() {
+        assert_eq!(
+            sample_toml::<TestConfig>(),
+            "# The port to listen on.
+# Must be a number >= 1 and <= 65535.
+port = 8080
+
+# Must not be empty.
+# Required.
+# tags = []
+
+# Must be `debug` or `info`.
+# level = \"\"
+
+# The database.
+[database]
+# Must be a URL with scheme `postgres`.
+# Required.
+# url = \"\"
+
+# password = \"\"
+"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Directory trees with a file per key, such as mounted Kubernetes
+//! ConfigMaps.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use figment::value::Dict;
+use figment::value::Map;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+use figment::Source;
+
+use crate::field;
+
+/// The link that Kubernetes swaps atomically to update a mounted volume.
+#[cfg(feature = "reload")]
+const DATA_LINK: &str = "..data";
+
+/// A provider for a directory tree with a file per key.
+///
+/// The path of a file within the directory is its key path: both
+/// subdirectories and dots in file names nest keys, e.g. `database/url` and
+/// `database.url` set `database.url`. The contents of a file, without its
+/// trailing newline, are its value, read as a number or a boolean when they
+/// are one.
+///
+/// Hidden files and directories are skipped, and so are the `..data` link
+/// and the timestamped directories that Kubernetes mounts volumes with.
+/// Links are followed. The values apply to every profile.
+pub struct DirectoryTree {
+    path: PathBuf,
+}
+
+impl DirectoryTree {
+    /// Create a new `DirectoryTree` for the directory at a path.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Get the paths of the files of the tree, and of the `..data` link if
+    /// there is one.
+    ///
+    /// A mounted volume is updated by swapping the link, so it is watched for
+    /// changes along with the files.
+    #[cfg(feature = "reload")]
+    pub(crate) fn paths(&self) -> Vec<PathBuf> {
+        fn walk(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+            for entry in entries(dir)? {
+                if fs::metadata(&entry)?.is_dir() {
+                    walk(&entry, paths)?;
+                } else {
+                    paths.push(entry);
+                }
+            }
+            Ok(())
+        }
+
+        let mut paths = Vec::new();
+        let data = self.path.join(DATA_LINK);
+        if data.exists() {
+            paths.push(data);
+        }
+        // a tree that cannot be read is reported when it is loaded
+        let _ = walk(&self.path, &mut paths);
+        paths
+    }
+}
+
+impl Provider for DirectoryTree {
+    fn metadata(&self) -> Metadata {
+        Metadata::from("directory tree", Source::File(self.path.clone()))
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        let values = read(&self.path).map_err(|e| {
+            figment::Error::from(format!(
+                "failed to read the directory tree {}: {}",
+                self.path.display(),
+                e
+            ))
+        })?;
+        Ok(Profile::Global.collect(values))
+    }
+}
+
+/// Read the values of the directory tree at `dir`.
+pub(crate) fn read(dir: &Path) -> io::Result<Dict> {
+    fn walk(dir: &Path, prefix: &str, values: &mut Dict) -> io::Result<()> {
+        for entry in entries(dir)? {
+            let name = match entry.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            let path = format!("{}{}", prefix, name);
+            if fs::metadata(&entry)?.is_dir() {
+                walk(&entry, &format!("{}.", path), values)?;
+            } else {
+                let contents = fs::read_to_string(&entry)?;
+                let value = contents
+                    .trim_end_matches(&['\r', '\n'][..])
+                    .parse()
+                    .expect("parsing a value is infallible");
+                field::insert(values, &path, value);
+            }
+        }
+        Ok(())
+    }
+
+    let mut values = Dict::new();
+    walk(dir, "", &mut values)?;
+    Ok(values)
+}
+
+/// Get the entries of a directory that are not hidden, in order.
+fn entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        if !hidden {
+            entries.push(path);
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use crate::AppConfig;
+    use crate::Layer;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        port: u16,
+        debug: bool,
+        database: Database,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Database {
+        url: String,
+        pool: u32,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+    }
+
+    /// Mount `files` in `mount` like Kubernetes does: in a timestamped
+    /// directory, behind the `..data` link that is swapped atomically.
+    fn mount(jail: &Jail, version: &str, files: &[(&str, &str)]) -> figment::Result<()> {
+        let dir = format!("mount/..{}", version);
+        jail.create_dir(&dir)?;
+        for (name, contents) in files {
+            jail.create_file(format!("{}/{}", dir, name), contents)?;
+            let link = format!("mount/{}", name);
+            if fs::symlink_metadata(&link).is_err() {
+                symlink(format!("..data/{}", name), link).unwrap();
+            }
+        }
+        symlink(format!("..{}", version), "mount/..data_tmp").unwrap();
+        fs::rename("mount/..data_tmp", "mount/..data").unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree() {
+        Jail::expect_with(|jail| {
+            mount(
+                jail,
+                "2024_01_01",
+                &[
+                    ("port", "8080\n"),
+                    ("debug", "true"),
+                    ("database.url", "postgres://db"),
+                ],
+            )?;
+            jail.create_dir("mount/database")?;
+            jail.create_file("mount/database/pool", "4")?;
+            jail.create_file("mount/.hidden", "x")?;
+
+            let loader = TestConfig::loader().directory("mount");
+            let config = loader.load().unwrap();
+            assert_eq!(config.port, 8080);
+            assert!(config.debug);
+            assert_eq!(config.database.url, "postgres://db");
+            assert_eq!(config.database.pool, 4);
+
+            let explanation = loader.explain().unwrap();
+            let port = explanation.get("port").unwrap();
+            assert_eq!(port.layer(), Some(Layer::File));
+            assert!(port.origin().unwrap().ends_with("mount"));
+
+            jail.create_file("mount/port", "high")?;
+            let err = TestConfig::loader().directory("mount").load().unwrap_err();
+            assert_eq!(err.layer(), Some(Layer::File));
+
+            let err = TestConfig::loader()
+                .directory("missing")
+                .load()
+                .unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("failed to read the directory tree"));
+            Ok(())
+        });
+    }
+
+    #[cfg(feature = "reload")]
+    #[test]
+    fn test_tree_reload() {
+        use std::time::Duration;
+
+        use crate::reload::ReloadOptions;
+
+        Jail::expect_with(|jail| {
+            let mut files = vec![
+                ("port", "1"),
+                ("debug", "false"),
+                ("database.url", "a"),
+                ("database.pool", "1"),
+            ];
+            mount(jail, "2024_01_01", &files)?;
+
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let options = ReloadOptions::new().interval(Duration::from_millis(10));
+                    let (reloader, worker) = TestConfig::loader()
+                        .directory("mount")
+                        .reloading(options)
+                        .unwrap();
+                    let mut rx = reloader.subscribe();
+                    tokio::spawn(worker);
+                    assert_eq!(reloader.current().port, 1);
+
+                    files[0] = ("port", "2");
+                    mount(jail, "2024_01_02", &files).unwrap();
+                    tokio::time::timeout(Duration::from_secs(5), rx.changed())
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(rx.borrow().config().port, 2);
+                    assert_eq!(rx.borrow().changed(), ["port"]);
+                });
+            Ok(())
+        });
+    }
+}
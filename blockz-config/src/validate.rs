@@ -0,0 +1,362 @@
+//! Validation of app configurations.
+
+use std::fmt;
+use std::fmt::Display;
+use std::ops::Bound;
+use std::ops::RangeBounds;
+
+use figment::value::Num;
+use figment::value::Value;
+use figment::Figment;
+use regex::Regex;
+
+use crate::Field;
+use crate::Layer;
+
+/// A constraint on the value of a field.
+///
+/// Constraints on a list apply to each of its items, except for
+/// [`Constraint::NonEmpty`] and [`Constraint::Custom`], which apply to the
+/// list itself.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Constraint {
+    /// The value must be a number in a range.
+    Range {
+        /// The lower bound of the range.
+        start: Bound<f64>,
+        /// The upper bound of the range.
+        end: Bound<f64>,
+    },
+    /// The value must not be an empty string, list or map.
+    NonEmpty,
+    /// The value must be a string that matches a regular expression.
+    Regex(&'static str),
+    /// The value must be a URL with one of the schemes, or with any scheme if
+    /// there are none.
+    Url(&'static [&'static str]),
+    /// The value must be one of the strings.
+    OneOf(&'static [&'static str]),
+    /// The value must pass a custom check, which returns the reason it failed.
+    Custom(fn(&Value) -> Result<(), String>),
+}
+
+impl Constraint {
+    /// Create a constraint that the value must be a number in `range`.
+    pub fn range<N: Into<f64> + Copy, R: RangeBounds<N>>(range: R) -> Self {
+        fn bound<N: Into<f64> + Copy>(bound: Bound<&N>) -> Bound<f64> {
+            match bound {
+                Bound::Included(n) => Bound::Included((*n).into()),
+                Bound::Excluded(n) => Bound::Excluded((*n).into()),
+                Bound::Unbounded => Bound::Unbounded,
+            }
+        }
+
+        Self::Range {
+            start: bound(range.start_bound()),
+            end: bound(range.end_bound()),
+        }
+    }
+
+    /// Check a value against this constraint.
+    ///
+    /// Returns the reason the value does not satisfy the constraint.
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        match (self, value) {
+            (Self::NonEmpty, value) => match value {
+                Value::String(_, s) if s.is_empty() => Err(self.to_string()),
+                Value::Array(_, items) if items.is_empty() => Err(self.to_string()),
+                Value::Dict(_, dict) if dict.is_empty() => Err(self.to_string()),
+                _ => Ok(()),
+            },
+            (Self::Custom(check), value) => check(value),
+            (_, Value::Array(_, items)) => items.iter().try_for_each(|item| self.check(item)),
+            (Self::Range { start, end }, value) => {
+                let n = match value.to_num_lossy().and_then(to_f64) {
+                    Some(n) => n,
+                    None => return Err("must be a number".to_string()),
+                };
+                if (*start, *end).contains(&n) {
+                    Ok(())
+                } else {
+                    Err(self.to_string())
+                }
+            }
+            (Self::Regex(pattern), Value::String(_, s)) => match Regex::new(pattern) {
+                Ok(regex) if regex.is_match(s) => Ok(()),
+                Ok(_) => Err(self.to_string()),
+                Err(e) => Err(format!("invalid pattern `{}`: {}", pattern, e)),
+            },
+            (Self::Url(schemes), Value::String(_, s)) => match url::Url::parse(s) {
+                Ok(url) if schemes.is_empty() || schemes.contains(&url.scheme()) => Ok(()),
+                Ok(_) => Err(self.to_string()),
+                Err(e) => Err(format!("must be a URL: {}", e)),
+            },
+            (Self::OneOf(values), Value::String(_, s)) if values.contains(&s.as_str()) => Ok(()),
+            (Self::Regex(_), _) | (Self::Url(_), _) | (Self::OneOf(_), _) => Err(self.to_string()),
+        }
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Range { start, end } => {
+                f.write_str("must be a number")?;
+                match start {
+                    Bound::Included(n) => write!(f, " >= {}", n)?,
+                    Bound::Excluded(n) => write!(f, " > {}", n)?,
+                    Bound::Unbounded => {}
+                }
+                if start != &Bound::Unbounded && end != &Bound::Unbounded {
+                    f.write_str(" and")?;
+                }
+                match end {
+                    Bound::Included(n) => write!(f, " <= {}", n),
+                    Bound::Excluded(n) => write!(f, " < {}", n),
+                    Bound::Unbounded => Ok(()),
+                }
+            }
+            Self::NonEmpty => f.write_str("must not be empty"),
+            Self::Regex(pattern) => write!(f, "must match `{}`", pattern),
+            Self::Url([]) => f.write_str("must be a URL"),
+            Self::Url(schemes) => write!(f, "must be a URL with scheme {}", Alternatives(schemes)),
+            Self::OneOf(values) => write!(f, "must be {}", Alternatives(values)),
+            Self::Custom(_) => f.write_str("must pass a custom check"),
+        }
+    }
+}
+
+/// Convert any number to an `f64`.
+fn to_f64(num: Num) -> Option<f64> {
+    num.to_f64()
+        .or_else(|| num.to_u128().map(|n| n as f64))
+        .or_else(|| num.to_i128().map(|n| n as f64))
+}
+
+/// Formats a list of alternatives, e.g. "`a`, `b` or `c`".
+struct Alternatives<'a>(&'a [&'a str]);
+
+impl Display for Alternatives<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, alternative) in self.0.iter().enumerate() {
+            if i > 0 && i + 1 == self.0.len() {
+                f.write_str(" or ")?;
+            } else if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{}`", alternative)?;
+        }
+        Ok(())
+    }
+}
+
+/// A value that failed validation.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    path: String,
+    message: String,
+    layer: Option<Layer>,
+    env: Option<String>,
+}
+
+impl Violation {
+    /// Create a new violation for the value at a key path.
+    ///
+    /// Nested keys are separated by dots, e.g. `server.port`.
+    pub fn new<P: Into<String>, M: Into<String>>(path: P, message: M) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            layer: None,
+            env: None,
+        }
+    }
+
+    /// Get the key path of the value.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the reason the value is invalid.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the layer that supplied the value, if it is known.
+    pub fn layer(&self) -> Option<Layer> {
+        self.layer
+    }
+
+    /// Get the environment variable that supplied the value, if it came from
+    /// the environment.
+    pub fn env(&self) -> Option<&str> {
+        self.env.as_deref()
+    }
+
+    /// Find the layer and the environment variable that supplied the value.
+    pub(crate) fn locate(mut self, figment: &Figment) -> Self {
+        if let Some(metadata) = figment.find_metadata(&self.path) {
+            self.layer = Layer::from_name(&metadata.name);
+            if self.layer == Some(Layer::Env) {
+                let keys: Vec<&str> = self.path.split('.').collect();
+                self.env = Some(metadata.interpolate(&figment.profile().clone(), &keys));
+            }
+        }
+        self
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` {}", self.path, self.message)?;
+        match (&self.env, self.layer) {
+            (Some(env), _) => write!(f, " (set by `{}`)", env),
+            (None, Some(layer)) => write!(f, " (set by the {})", layer),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Check the values of `fields` in `figment` against their constraints.
+pub(crate) fn check_fields(figment: &Figment, fields: &[Field]) -> Vec<Violation> {
+    fn walk(figment: &Figment, fields: &[Field], prefix: &str, violations: &mut Vec<Violation>) {
+        for field in fields {
+            let path = format!("{}{}", prefix, field.name);
+            if field.is_section() {
+                walk(figment, &field.fields, &format!("{}.", path), violations);
+                continue;
+            }
+
+            let value = match figment.find_value(&path) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for constraint in &field.constraints {
+                if let Err(message) = constraint.check(&value) {
+                    violations.push(Violation::new(path.clone(), message));
+                }
+            }
+        }
+    }
+
+    let mut violations = Vec::new();
+    walk(figment, fields, "", &mut violations);
+    violations
+}
+
+#[cfg(test)]
+mod test {
+    use figment::value::Value;
+    use figment::Jail;
+    use serde::Deserialize;
+
+    use super::Constraint;
+    use super::Violation;
+    use crate::AppConfig;
+    use crate::Field;
+    use crate::Layer;
+
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        port: u16,
+        #[allow(dead_code)]
+        url: String,
+        min: u32,
+        max: u32,
+    }
+
+    impl AppConfig for TestConfig {
+        const PACKAGE: &'static str = "APP_";
+
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new("port", "u16")
+                    .with_default(Value::from(80))
+                    .with_constraint(Constraint::range(1..=65535)),
+                Field::new("url", "String")
+                    .with_constraint(Constraint::NonEmpty)
+                    .with_constraint(Constraint::Url(&["https"])),
+                Field::new("min", "u32").with_default(Value::from(1)),
+                Field::new("max", "u32").with_default(Value::from(10)),
+            ]
+        }
+
+        fn validate(&self) -> Result<(), Vec<Violation>> {
+            if self.min > self.max {
+                return Err(vec![Violation::new("min", "must not exceed `max`")]);
+            }
+            Ok(())
+        }
+    }
+
+    fn check(constraint: Constraint, value: Value) -> Result<(), String> {
+        constraint.check(&value)
+    }
+
+    #[test]
+    fn test_constraint_range() {
+        assert!(check(Constraint::range(1..=65535), Value::from(8080)).is_ok());
+        assert!(check(Constraint::range(1..=65535), Value::from("8080")).is_ok());
+        assert_eq!(
+            check(Constraint::range(1..=65535), Value::from(0)),
+            Err("must be a number >= 1 and <= 65535".to_string())
+        );
+        assert!(check(Constraint::range(0.0..1.0), Value::from(1.0)).is_err());
+        assert!(check(Constraint::range(..10), Value::from(vec![1, 20])).is_err());
+    }
+
+    #[test]
+    fn test_constraint_strings() {
+        assert!(check(Constraint::NonEmpty, Value::from("")).is_err());
+        assert!(check(Constraint::NonEmpty, Value::from(Vec::<u8>::new())).is_err());
+        assert!(check(Constraint::Regex("^[a-z]+$"), Value::from("abc")).is_ok());
+        assert!(check(Constraint::Regex("^[a-z]+$"), Value::from("ABC")).is_err());
+        assert!(check(Constraint::OneOf(&["a", "b"]), Value::from("b")).is_ok());
+        assert_eq!(
+            check(Constraint::OneOf(&["a", "b", "c"]), Value::from("d")),
+            Err("must be `a`, `b` or `c`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_constraint_url() {
+        let url = Constraint::Url(&["postgres", "postgresql"]);
+        assert!(check(url.clone(), Value::from("postgres://db/app")).is_ok());
+        assert!(check(url.clone(), Value::from("mysql://db/app")).is_err());
+        assert!(check(url, Value::from("")).is_err());
+        assert!(check(Constraint::Url(&[]), Value::from("redis://cache")).is_ok());
+    }
+
+    #[test]
+    fn test_validate_aggregated() {
+        Jail::expect_with(|jail| {
+            jail.create_file("config.toml", "url = ''\nmin = 20")?;
+            jail.set_env("APP_PORT", 0);
+
+            let err = TestConfig::load().unwrap_err();
+            let violations: Vec<_> = err
+                .violations()
+                .iter()
+                .map(|violation| (violation.path(), violation.layer(), violation.env()))
+                .collect();
+            assert_eq!(
+                violations,
+                vec![
+                    ("port", Some(Layer::Env), Some("APP_PORT")),
+                    ("url", Some(Layer::File), None),
+                    ("url", Some(Layer::File), None),
+                    ("min", Some(Layer::File), None),
+                ]
+            );
+            assert!(err
+                .to_string()
+                .contains("`port` must be a number >= 1 and <= 65535 (set by `APP_PORT`)"));
+
+            jail.create_file("config.toml", "url = 'https://example.com'")?;
+            jail.set_env("APP_PORT", 8080);
+            assert_eq!(TestConfig::load().unwrap().port, 8080);
+            Ok(())
+        });
+    }
+}
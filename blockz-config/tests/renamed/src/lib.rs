@@ -0,0 +1,25 @@
+//! Test the `config` macro through a renamed dependency.
+
+app_config::config!("renamed-app" {
+    /// The port to listen on.
+    port: u16 = 8080,
+    /// The database.
+    database: Database {
+        /// The URL of the database.
+        url: String = "postgres://localhost".to_string(),
+    },
+});
+
+#[cfg(test)]
+mod test {
+    use app_config::AppConfig;
+
+    use super::Config;
+
+    #[test]
+    fn test_renamed_dependency() {
+        let config = Config::load().unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.database.url, "postgres://localhost");
+    }
+}
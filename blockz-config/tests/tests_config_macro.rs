@@ -1,6 +1,227 @@
 //! Test the `config` macro.
 
+// `Jail` closures return `figment::Error`
+#![allow(clippy::result_large_err)]
+
 #[macro_use]
 extern crate blockz_config;
 
 config!("blockz-config");
+
+mod declared {
+    use blockz_config::AppConfig;
+
+    config!("DECLARED_" {
+        /// The port to listen on.
+        port: u16 = 8080,
+        /// The name of the service.
+        name: String = "declared".into(),
+        tags: Vec<String>,
+        /// The database settings.
+        database: Database {
+            /// The URL of the database.
+            #[env = "DECLARED_TEST_DATABASE_URL"]
+            url: String,
+            pool: Pool {
+                size: u32 = 4,
+            },
+        },
+    });
+
+    #[test]
+    fn test_config_macro_default() {
+        let config = Config::default();
+
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.name, "declared");
+        assert!(config.tags.is_empty());
+        assert_eq!(config.database.url, "");
+        assert_eq!(config.database.pool.size, 4);
+    }
+
+    #[test]
+    fn test_config_macro_fields() {
+        let fields = Config::fields();
+
+        let names: Vec<_> = fields.iter().map(|field| field.name).collect();
+        assert_eq!(names, vec!["port", "name", "tags", "database"]);
+        assert_eq!(fields[0].ty, "u16");
+        assert_eq!(fields[0].doc(), "The port to listen on.");
+        assert!(fields[2].default.is_none());
+
+        let database = &fields[3];
+        assert!(database.is_section());
+        assert_eq!(database.fields[0].env, Some("DECLARED_TEST_DATABASE_URL"));
+        assert!(database.fields[1].is_section());
+    }
+
+    #[test]
+    fn test_config_macro_load() {
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("DECLARED_TAGS", "[a, b]");
+            jail.set_env("DECLARED_TEST_DATABASE_URL", "postgres://db");
+
+            let config = Config::load().unwrap();
+            assert_eq!(config.port, 8080);
+            assert_eq!(config.tags, vec!["a", "b"]);
+            assert_eq!(config.database.url, "postgres://db");
+            assert_eq!(config.database.pool.size, 4);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_config_macro_missing_field() {
+        figment::Jail::expect_with(|_| {
+            let err = Config::load().unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("missing field `database.url` of type `String`"));
+            let envs: Vec<_> = err.issues().iter().map(|issue| issue.env()).collect();
+            assert_eq!(envs, ["DECLARED_TAGS", "DECLARED_DATABASE__URL"]);
+            Ok(())
+        });
+    }
+}
+
+mod validated {
+    use blockz_config::AppConfig;
+    use blockz_config::Violation;
+
+    config!(
+        "VALIDATED_",
+        validate = check_workers {
+            #[validate(range(1..=65535))]
+            port: u16 = 8080,
+            #[validate(one_of("debug", "info", "warn"))]
+            level: String = "info".into(),
+            #[validate(custom = even)]
+            #[validate(range(1..))]
+            min_workers: u32 = 2,
+            max_workers: u32 = 8,
+            database: Database {
+                #[env = "VALIDATED_TEST_DATABASE_URL"]
+                #[validate(non_empty)]
+                #[validate(url("postgres"))]
+                url: String = "postgres://db".into(),
+            },
+        }
+    );
+
+    fn even(n: &u32) -> Result<(), String> {
+        if n.is_multiple_of(2) {
+            Ok(())
+        } else {
+            Err("must be even".to_string())
+        }
+    }
+
+    fn check_workers(config: &Config) -> Result<(), Vec<Violation>> {
+        if config.min_workers > config.max_workers {
+            return Err(vec![Violation::new(
+                "min_workers",
+                "must not exceed `max_workers`",
+            )]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_macro_validate() {
+        figment::Jail::expect_with(|jail| {
+            assert_eq!(Config::load().unwrap().port, 8080);
+
+            jail.set_env("VALIDATED_LEVEL", "trace");
+            jail.set_env("VALIDATED_MIN_WORKERS", 9);
+            jail.set_env("VALIDATED_TEST_DATABASE_URL", "mysql://db");
+
+            let err = Config::load().unwrap_err();
+            let violations: Vec<_> = err
+                .violations()
+                .iter()
+                .map(|violation| (violation.path(), violation.env()))
+                .collect();
+            assert_eq!(
+                violations,
+                vec![
+                    ("level", Some("VALIDATED_LEVEL")),
+                    ("min_workers", Some("VALIDATED_MIN_WORKERS")),
+                    ("database.url", Some("VALIDATED_TEST_DATABASE_URL")),
+                    ("min_workers", Some("VALIDATED_MIN_WORKERS")),
+                ]
+            );
+            assert_eq!(err.violations()[1].message(), "must be even");
+            Ok(())
+        });
+    }
+}
+
+mod unnamed {
+    use blockz_config::AppConfig;
+
+    config!({
+        port: u16 = 8080,
+    });
+
+    #[test]
+    fn test_config_macro_package_name() {
+        assert_eq!(Config::PACKAGE, "blockz-config");
+        assert_eq!(blockz_config::env_prefix::<Config>(), "BLOCKZ_CONFIG_");
+        assert_eq!(
+            blockz_config::env_var::<Config>("port"),
+            "BLOCKZ_CONFIG_PORT"
+        );
+
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("BLOCKZ_CONFIG_PORT", 80);
+            assert_eq!(Config::load().unwrap().port, 80);
+            Ok(())
+        });
+    }
+}
+
+mod prefixed {
+    use blockz_config::AppConfig;
+
+    config!("prefixed-app", prefix = "PFX_" {
+        port: u16 = 8080,
+    });
+
+    #[test]
+    fn test_config_macro_prefix() {
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("PREFIXED_APP_PORT", 80);
+            assert_eq!(Config::load().unwrap().port, 8080);
+
+            jail.set_env("PFX_PORT", 81);
+            assert_eq!(Config::load().unwrap().port, 81);
+            Ok(())
+        });
+    }
+}
+
+mod strict {
+    use blockz_config::AppConfig;
+    use blockz_config::IssueKind;
+
+    config!(
+        "strict-app",
+        prefix = "STRICT_",
+        strict = Deny {
+            log_level: String = "info".into(),
+        }
+    );
+
+    #[test]
+    fn test_config_macro_strict() {
+        figment::Jail::expect_with(|jail| {
+            assert_eq!(Config::load().unwrap().log_level, "info");
+
+            jail.set_env("STRICT_LOG_LEVLE", "debug");
+            let err = Config::load().unwrap_err();
+            assert_eq!(err.issues()[0].kind(), IssueKind::Unknown);
+            assert_eq!(err.issues()[0].suggestion(), Some("STRICT_LOG_LEVEL"));
+            Ok(())
+        });
+    }
+}
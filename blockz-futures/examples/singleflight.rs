@@ -0,0 +1,42 @@
+//! Example usage of `SingleFlight` for deduplicating concurrent calls.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use blockz_futures::singleflight::SingleFlight;
+use blockz_futures::BlockzFutureExt;
+
+#[tokio::main]
+async fn main() {
+    let flight = SingleFlight::new();
+    let computed = Arc::new(AtomicUsize::new(0));
+
+    // dummy expensive computation that takes 3 ms
+    let lookup = |flight: &SingleFlight<&'static str, u64>| {
+        let computed = computed.clone();
+        flight.call("answer", move || async move {
+            println!("computation: start");
+            tokio::time::sleep(Duration::from_millis(3)).await;
+            computed.fetch_add(1, Ordering::SeqCst);
+            42
+        })
+    };
+
+    // two patient callers and one caller that gives up after 1 ms
+    let (first, second, impatient) = tokio::join!(
+        lookup(&flight),
+        lookup(&flight),
+        lookup(&flight).timeout(Duration::from_millis(1)),
+    );
+
+    // the impatient caller timed out, but the computation kept going for the
+    // other callers
+    assert!(impatient.is_err());
+    assert_eq!((first, second), (42, 42));
+
+    // the computation ran only once
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+    println!("main: computed once, shared by every caller");
+}
@@ -6,12 +6,74 @@ use std::task::Context;
 use std::task::Poll;
 
 use thiserror::Error;
-use tokio::sync::oneshot;
+
+use self::reason::IntoCancelReason;
+use crate::probe::Interrupt;
+use crate::probe::Probe;
+use crate::rt::oneshot;
 
 /// Error type for futures that can be canceled.
 #[derive(Clone, Copy, Debug, Error)]
 #[error("future has been canceled")]
-pub struct Canceled(());
+pub struct Canceled(CancelReason);
+
+impl Canceled {
+    /// Get the reason the future has been canceled for.
+    pub fn reason(&self) -> CancelReason {
+        self.0
+    }
+}
+
+/// The reason a future has been canceled for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelReason {
+    /// A cancellation signal has been sent through a cancel handle or channel.
+    Signaled,
+    /// The cancel handle or the sending half of the cancel channel has been
+    /// dropped.
+    Dropped,
+    /// The cancel future completed.
+    Future,
+}
+
+impl CancelReason {
+    /// Get the name of this reason.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Signaled => "signaled",
+            Self::Dropped => "dropped",
+            Self::Future => "future",
+        }
+    }
+}
+
+/// Conversions of the outputs of cancel futures into cancel reasons.
+///
+/// The trait is public in a private module, so that it can bound the public
+/// `Future` impl of [`Cancel`] without being part of the public API.
+mod reason {
+    use super::CancelReason;
+
+    /// The output of a cancel future.
+    pub trait IntoCancelReason {
+        /// Get the reason the future has been canceled for.
+        fn into_cancel_reason(self) -> CancelReason;
+    }
+
+    /// Cancel futures given by the caller complete with `()`.
+    impl IntoCancelReason for () {
+        fn into_cancel_reason(self) -> CancelReason {
+            CancelReason::Future
+        }
+    }
+
+    /// Cancel channels tell whether they were signaled or dropped.
+    impl IntoCancelReason for CancelReason {
+        fn into_cancel_reason(self) -> CancelReason {
+            self
+        }
+    }
+}
 
 /// A future that can be canceled.
 #[pin_project]
@@ -20,6 +82,7 @@ pub struct Cancel<F, C> {
     future: F,
     #[pin]
     cancel: C,
+    probe: Probe,
 }
 
 impl<F> Cancel<F, CancelChannelFuture> {
@@ -27,35 +90,62 @@ impl<F> Cancel<F, CancelChannelFuture> {
     pub(crate) fn new(future: F) -> (Self, CancelHandle) {
         let (tx, rx) = oneshot::channel();
         let cancel = CancelChannelFuture::new(rx);
-        (Self { future, cancel }, CancelHandle::new(tx))
+        let probe = Probe::new();
+        (
+            Self {
+                future,
+                cancel,
+                probe,
+            },
+            CancelHandle::new(tx),
+        )
     }
 
     /// Create a `Cancel` future with a `cancel` channel.
     pub(crate) fn with_cancel_channel(future: F, cancel: oneshot::Receiver<()>) -> Self {
         let cancel = CancelChannelFuture::new(cancel);
-        Self { future, cancel }
+        let probe = Probe::new();
+        Self {
+            future,
+            cancel,
+            probe,
+        }
     }
 }
 
 impl<F, C> Cancel<F, C> {
     /// Create a new `Cancel` future with a `cancel` future.
     pub(crate) fn with_cancel(future: F, cancel: C) -> Self {
-        Self { future, cancel }
+        let probe = Probe::new();
+        Self {
+            future,
+            cancel,
+            probe,
+        }
     }
 }
 
-impl<F: Future, C: Future<Output = ()>> Future for Cancel<F, C> {
+impl<F, C> Future for Cancel<F, C>
+where
+    F: Future,
+    C: Future,
+    C::Output: IntoCancelReason,
+{
     type Output = Result<F::Output, Canceled>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
         let future: Pin<&mut F> = this.future;
         let cancel: Pin<&mut C> = this.cancel;
+        let probe: &Probe = this.probe;
+        let _entered = probe.enter();
 
         if let Poll::Ready(out) = future.poll(cx) {
             Poll::Ready(Ok(out))
-        } else if cancel.poll(cx).is_ready() {
-            Poll::Ready(Err(Canceled(())))
+        } else if let Poll::Ready(reason) = cancel.poll(cx) {
+            let reason = reason.into_cancel_reason();
+            probe.fired(Interrupt::Cancel(reason));
+            Poll::Ready(Err(Canceled(reason)))
         } else {
             Poll::Pending
         }
@@ -75,12 +165,15 @@ impl CancelChannelFuture {
 }
 
 impl Future for CancelChannelFuture {
-    type Output = ();
+    type Output = CancelReason;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
         let rx: Pin<&mut oneshot::Receiver<()>> = this.0;
-        rx.poll(cx).map(|_| ())
+        rx.poll(cx).map(|result| match result {
+            Ok(()) => CancelReason::Signaled,
+            Err(_) => CancelReason::Dropped,
+        })
     }
 }
 
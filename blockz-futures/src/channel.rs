@@ -0,0 +1,321 @@
+//! Interruptible sends and receives for tokio channels.
+//!
+//! The extensions in this module return the crate's interrupt errors instead
+//! of nesting the channel errors inside the interrupt errors. A send that is
+//! interrupted hands back the value that could not be sent.
+
+use std::future::Future;
+use std::time::Duration;
+use std::time::Instant;
+
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+
+use crate::cancel::Canceled;
+use crate::timeout::TimedOut;
+use crate::BlockzFutureExt;
+
+/// Error type for interruptible sends.
+///
+/// Every variant holds the value that could not be sent.
+#[derive(Debug, Error)]
+pub enum SendError<T> {
+    /// The receiving half of the channel has been closed.
+    #[error("channel closed")]
+    Closed(T),
+    /// The send ran out of time.
+    #[error("send interrupted: {1}")]
+    TimedOut(T, #[source] TimedOut),
+    /// The send has been canceled.
+    #[error("send interrupted: {1}")]
+    Canceled(T, #[source] Canceled),
+}
+
+impl<T> SendError<T> {
+    /// Get back the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Closed(value) => value,
+            Self::TimedOut(value, _) => value,
+            Self::Canceled(value, _) => value,
+        }
+    }
+}
+
+/// Error type for interruptible receives.
+#[derive(Clone, Copy, Debug, Error)]
+pub enum RecvError {
+    /// The sending half of the channel has been closed.
+    #[error("channel closed")]
+    Closed,
+    /// The receiver lagged behind and missed some messages.
+    ///
+    /// This is only returned by broadcast channels.
+    #[error("receiver lagged behind by {0} message(s)")]
+    Lagged(u64),
+    /// The receive ran out of time.
+    #[error("receive interrupted: {0}")]
+    TimedOut(#[from] TimedOut),
+    /// The receive has been canceled.
+    #[error("receive interrupted: {0}")]
+    Canceled(#[from] Canceled),
+}
+
+/// Interruptible sends for [`mpsc::Sender`].
+///
+/// The value is only handed to the channel once there is capacity for it, so
+/// it can be returned if the send is interrupted.
+pub trait SenderExt<T>: private::Sealed {
+    /// Send a value, waiting for capacity in a time interval.
+    ///
+    /// This is not named `send_timeout` because [`mpsc::Sender`] already has
+    /// an inherent method with that name, which would shadow this one.
+    fn send_with_timeout(
+        &self,
+        value: T,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<(), SendError<T>>> + '_;
+
+    /// Send a value, waiting for capacity until a point in time.
+    fn send_deadline(
+        &self,
+        value: T,
+        deadline: Instant,
+    ) -> impl Future<Output = Result<(), SendError<T>>> + '_;
+
+    /// Send a value, waiting for capacity until the `cancel` future completes.
+    fn send_with_cancel<'a, C: Future<Output = ()> + 'a>(
+        &'a self,
+        value: T,
+        cancel: C,
+    ) -> impl Future<Output = Result<(), SendError<T>>> + 'a;
+}
+
+impl<T> SenderExt<T> for mpsc::Sender<T> {
+    async fn send_with_timeout(&self, value: T, timeout: Duration) -> Result<(), SendError<T>> {
+        match self.reserve().timeout(timeout).await {
+            Ok(Ok(permit)) => {
+                permit.send(value);
+                Ok(())
+            }
+            Ok(Err(_)) => Err(SendError::Closed(value)),
+            Err(e) => Err(SendError::TimedOut(value, e)),
+        }
+    }
+
+    async fn send_deadline(&self, value: T, deadline: Instant) -> Result<(), SendError<T>> {
+        match self.reserve().deadline(deadline).await {
+            Ok(Ok(permit)) => {
+                permit.send(value);
+                Ok(())
+            }
+            Ok(Err(_)) => Err(SendError::Closed(value)),
+            Err(e) => Err(SendError::TimedOut(value, e)),
+        }
+    }
+
+    async fn send_with_cancel<'a, C: Future<Output = ()> + 'a>(
+        &'a self,
+        value: T,
+        cancel: C,
+    ) -> Result<(), SendError<T>> {
+        match self.reserve().with_cancel_future(cancel).await {
+            Ok(Ok(permit)) => {
+                permit.send(value);
+                Ok(())
+            }
+            Ok(Err(_)) => Err(SendError::Closed(value)),
+            Err(e) => Err(SendError::Canceled(value, e)),
+        }
+    }
+}
+
+/// Interruptible receives for tokio channels.
+///
+/// This is implemented for [`mpsc::Receiver`], [`mpsc::UnboundedReceiver`],
+/// [`oneshot::Receiver`] and [`broadcast::Receiver`]. An interrupted receive
+/// leaves the receiver usable, except for a oneshot receiver, which must not
+/// be used again once it produced a value or reported the channel closed.
+pub trait ReceiverExt<T>: private::Sealed {
+    /// Receive the next value in a time interval.
+    fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<T, RecvError>> + '_ {
+        async move { self.recv_or_closed().timeout(timeout).await? }
+    }
+
+    /// Receive the next value before a point in time.
+    fn recv_deadline(
+        &mut self,
+        deadline: Instant,
+    ) -> impl Future<Output = Result<T, RecvError>> + '_ {
+        async move { self.recv_or_closed().deadline(deadline).await? }
+    }
+
+    /// Receive the next value before the `cancel` future completes.
+    fn recv_with_cancel<'a, C: Future<Output = ()> + 'a>(
+        &'a mut self,
+        cancel: C,
+    ) -> impl Future<Output = Result<T, RecvError>> + 'a {
+        async move { self.recv_or_closed().with_cancel_future(cancel).await? }
+    }
+
+    /// Receive the next value, mapping the channel errors to [`RecvError`].
+    #[doc(hidden)]
+    fn recv_or_closed(&mut self) -> impl Future<Output = Result<T, RecvError>> + '_;
+}
+
+impl<T> ReceiverExt<T> for mpsc::Receiver<T> {
+    async fn recv_or_closed(&mut self) -> Result<T, RecvError> {
+        self.recv().await.ok_or(RecvError::Closed)
+    }
+}
+
+impl<T> ReceiverExt<T> for mpsc::UnboundedReceiver<T> {
+    async fn recv_or_closed(&mut self) -> Result<T, RecvError> {
+        self.recv().await.ok_or(RecvError::Closed)
+    }
+}
+
+impl<T> ReceiverExt<T> for oneshot::Receiver<T> {
+    async fn recv_or_closed(&mut self) -> Result<T, RecvError> {
+        self.await.map_err(|_| RecvError::Closed)
+    }
+}
+
+impl<T: Clone> ReceiverExt<T> for broadcast::Receiver<T> {
+    async fn recv_or_closed(&mut self) -> Result<T, RecvError> {
+        self.recv().await.map_err(|e| match e {
+            broadcast::error::RecvError::Closed => RecvError::Closed,
+            broadcast::error::RecvError::Lagged(n) => RecvError::Lagged(n),
+        })
+    }
+}
+
+mod private {
+    use tokio::sync::broadcast;
+    use tokio::sync::mpsc;
+    use tokio::sync::oneshot;
+
+    pub trait Sealed {}
+
+    impl<T> Sealed for mpsc::Sender<T> {}
+    impl<T> Sealed for mpsc::Receiver<T> {}
+    impl<T> Sealed for mpsc::UnboundedReceiver<T> {}
+    impl<T> Sealed for oneshot::Receiver<T> {}
+    impl<T> Sealed for broadcast::Receiver<T> {}
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use tokio::sync::broadcast;
+    use tokio::sync::mpsc;
+    use tokio::sync::oneshot;
+
+    use super::ReceiverExt;
+    use super::RecvError;
+    use super::SendError;
+    use super::SenderExt;
+
+    #[tokio::test]
+    async fn test_mpsc_send_with_timeout_ok() {
+        let (tx, mut rx) = mpsc::channel(1);
+
+        assert!(tx
+            .send_with_timeout(1, Duration::from_millis(1))
+            .await
+            .is_ok());
+        assert_eq!(rx.recv().await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_mpsc_send_with_timeout_returns_value() {
+        let (tx, _rx) = mpsc::channel(1);
+        tx.send(1).await.unwrap();
+
+        let err = tx
+            .send_with_timeout(2, Duration::from_millis(1))
+            .await
+            .expect_err("channel should be full");
+        assert!(matches!(err, SendError::TimedOut(2, _)));
+    }
+
+    #[tokio::test]
+    async fn test_mpsc_send_with_cancel_returns_value() {
+        let (tx, _rx) = mpsc::channel(1);
+        tx.send(1).await.unwrap();
+
+        let err = tx
+            .send_with_cancel(2, async {})
+            .await
+            .expect_err("send should have been canceled");
+        assert_eq!(err.into_inner(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_mpsc_send_deadline_closed() {
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+
+        let err = tx
+            .send_deadline(1, Instant::now() + Duration::from_millis(500))
+            .await
+            .expect_err("channel should be closed");
+        assert!(matches!(err, SendError::Closed(1)));
+    }
+
+    #[tokio::test]
+    async fn test_mpsc_recv_timeout() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let result = rx.recv_timeout(Duration::from_millis(1)).await;
+        assert!(matches!(result, Err(RecvError::TimedOut(_))));
+
+        // the receiver is still usable after being interrupted
+        tx.send(1).unwrap();
+        let result = rx.recv_timeout(Duration::from_millis(500)).await;
+        assert!(matches!(result, Ok(1)));
+
+        drop(tx);
+        let result = rx.recv_timeout(Duration::from_millis(500)).await;
+        assert!(matches!(result, Err(RecvError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_oneshot_recv_with_cancel() {
+        let (_tx, mut rx) = oneshot::channel::<()>();
+
+        let result = rx.recv_with_cancel(async {}).await;
+        assert!(matches!(result, Err(RecvError::Canceled(_))));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_recv_deadline_lagged() {
+        let (tx, mut rx) = broadcast::channel(1);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(500);
+        assert!(matches!(
+            rx.recv_deadline(deadline).await,
+            Err(RecvError::Lagged(1))
+        ));
+        assert!(matches!(rx.recv_deadline(deadline).await, Ok(2)));
+    }
+
+    #[tokio::test]
+    async fn test_recv_is_send() {
+        let (tx, mut rx) = mpsc::channel(1);
+
+        let task = tokio::spawn(async move { rx.recv_timeout(Duration::from_millis(500)).await });
+        tx.send(1).await.unwrap();
+
+        assert!(matches!(task.await, Ok(Ok(1))));
+    }
+}
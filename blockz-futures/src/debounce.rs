@@ -0,0 +1,472 @@
+//! Debounce and throttle bursts of triggers.
+//!
+//! A [`Debouncer`] runs its action once a burst of triggers is followed by a
+//! quiet period. A [`Throttler`] runs its action at most once per rate window.
+//! Both hand out a [`Worker`] future that runs the action and must be spawned
+//! on an executor. The worker can be canceled with the crate's cancel types,
+//! e.g. [`BlockzFutureExt::with_cancel_handle`](crate::BlockzFutureExt).
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::time::Duration;
+use std::time::Instant;
+
+use futures::future::select;
+use futures::future::BoxFuture;
+use futures::future::Either;
+use futures::FutureExt;
+
+use crate::rt;
+
+/// Options for debouncers and throttlers.
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    leading: bool,
+    trailing: bool,
+    flush_on_shutdown: bool,
+}
+
+impl Options {
+    /// Create new options that run the action on the trailing edge only.
+    pub fn new() -> Self {
+        Self {
+            leading: false,
+            trailing: true,
+            flush_on_shutdown: false,
+        }
+    }
+
+    /// Run the action on the first trigger of a burst.
+    pub fn leading(mut self, leading: bool) -> Self {
+        self.leading = leading;
+        self
+    }
+
+    /// Run the action at the end of a burst, if there were triggers that did
+    /// not run it yet.
+    pub fn trailing(mut self, trailing: bool) -> Self {
+        self.trailing = trailing;
+        self
+    }
+
+    /// Run a pending trailing action right away when shutting down, instead of
+    /// dropping it.
+    pub fn flush_on_shutdown(mut self, flush_on_shutdown: bool) -> Self {
+        self.flush_on_shutdown = flush_on_shutdown;
+        self
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs an async action once a burst of triggers is followed by a quiet
+/// period.
+///
+/// Every trigger restarts the quiet period. The debouncer can be cloned and
+/// triggered from many tasks; it shuts down when [`Debouncer::shutdown`] is
+/// called or when every clone has been dropped.
+#[derive(Clone)]
+pub struct Debouncer {
+    handle: Arc<Handle>,
+}
+
+impl Debouncer {
+    /// Create a new `Debouncer` and the worker that runs `action`.
+    ///
+    /// The worker must be spawned for the action to run.
+    pub fn new<F, Fut>(period: Duration, options: Options, action: F) -> (Self, Worker)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let triggers = Triggers::new();
+        let handle = Arc::new(Handle(triggers.clone()));
+        let worker = debounce(triggers, period, options, action).boxed();
+        (Self { handle }, Worker(worker))
+    }
+
+    /// Trigger the action.
+    ///
+    /// Returns false if the debouncer has been shut down.
+    pub fn trigger(&self) -> bool {
+        self.handle.0.trigger()
+    }
+
+    /// Shut down the debouncer.
+    ///
+    /// The worker completes after flushing a pending action, if configured to
+    /// do so.
+    pub fn shutdown(&self) {
+        self.handle.0.close();
+    }
+}
+
+/// Runs an async action at most once per rate window.
+///
+/// The throttler can be cloned and triggered from many tasks; it shuts down
+/// when [`Throttler::shutdown`] is called or when every clone has been
+/// dropped.
+#[derive(Clone)]
+pub struct Throttler {
+    handle: Arc<Handle>,
+}
+
+impl Throttler {
+    /// Create a new `Throttler` and the worker that runs `action`.
+    ///
+    /// The worker must be spawned for the action to run.
+    pub fn new<F, Fut>(window: Duration, options: Options, action: F) -> (Self, Worker)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let triggers = Triggers::new();
+        let handle = Arc::new(Handle(triggers.clone()));
+        let worker = throttle(triggers, window, options, action).boxed();
+        (Self { handle }, Worker(worker))
+    }
+
+    /// Trigger the action.
+    ///
+    /// Returns false if the throttler has been shut down.
+    pub fn trigger(&self) -> bool {
+        self.handle.0.trigger()
+    }
+
+    /// Shut down the throttler.
+    ///
+    /// The worker completes after flushing a pending action, if configured to
+    /// do so.
+    pub fn shutdown(&self) {
+        self.handle.0.close();
+    }
+}
+
+/// Future that runs the action of a [`Debouncer`] or a [`Throttler`].
+///
+/// The worker completes once the debouncer or throttler has been shut down.
+pub struct Worker(BoxFuture<'static, ()>);
+
+impl Future for Worker {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().0.poll_unpin(cx)
+    }
+}
+
+/// Shuts down the triggers when the last debouncer or throttler is dropped.
+struct Handle(Triggers);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// What the worker has been woken up for.
+enum Signal {
+    Triggered,
+    Closed,
+}
+
+/// Triggers shared between the handles and the worker.
+#[derive(Clone)]
+struct Triggers(Arc<Mutex<TriggersState>>);
+
+struct TriggersState {
+    triggered: bool,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Triggers {
+    /// Create new triggers.
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(TriggersState {
+            triggered: false,
+            closed: false,
+            waker: None,
+        })))
+    }
+
+    /// Lock the state, ignoring poisoning.
+    fn lock(&self) -> MutexGuard<'_, TriggersState> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record a trigger and wake the worker.
+    fn trigger(&self) -> bool {
+        let mut state = self.lock();
+        if state.closed {
+            return false;
+        }
+        state.triggered = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        true
+    }
+
+    /// Close the triggers and wake the worker.
+    fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Wait for the next signal.
+    ///
+    /// Pending triggers are reported before the triggers being closed.
+    fn next(&self) -> impl Future<Output = Signal> + Unpin + '_ {
+        futures::future::poll_fn(move |cx| {
+            let mut state = self.lock();
+            if state.triggered {
+                state.triggered = false;
+                Poll::Ready(Signal::Triggered)
+            } else if state.closed {
+                Poll::Ready(Signal::Closed)
+            } else {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+
+    /// Wait until `deadline`, recording whether any triggers arrived.
+    ///
+    /// Returns `None` if the triggers have been closed in the meantime.
+    /// If `restart` is set, every trigger pushes the deadline back by that
+    /// duration.
+    async fn collect_until(
+        &self,
+        mut deadline: Instant,
+        restart: Option<Duration>,
+    ) -> Option<bool> {
+        let mut triggered = false;
+        loop {
+            let sleep = rt::sleep_until(deadline);
+            futures::pin_mut!(sleep);
+            match select(sleep, self.next()).await {
+                Either::Left(_) => return Some(triggered),
+                Either::Right((Signal::Triggered, _)) => {
+                    triggered = true;
+                    if let Some(period) = restart {
+                        deadline = Instant::now() + period;
+                    }
+                }
+                Either::Right((Signal::Closed, _)) => return None,
+            }
+        }
+    }
+}
+
+/// Drive a debouncer.
+async fn debounce<F, Fut>(triggers: Triggers, period: Duration, options: Options, mut action: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    loop {
+        if let Signal::Closed = triggers.next().await {
+            return;
+        }
+
+        let mut pending = true;
+        if options.leading {
+            action().await;
+            pending = false;
+        }
+
+        let deadline = Instant::now() + period;
+        match triggers.collect_until(deadline, Some(period)).await {
+            Some(triggered) => pending |= triggered,
+            None => {
+                if options.trailing && options.flush_on_shutdown && pending {
+                    action().await;
+                }
+                return;
+            }
+        }
+
+        if options.trailing && pending {
+            action().await;
+        }
+    }
+}
+
+/// Drive a throttler.
+async fn throttle<F, Fut>(triggers: Triggers, window: Duration, options: Options, mut action: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    loop {
+        if let Signal::Closed = triggers.next().await {
+            return;
+        }
+
+        let mut deadline = Instant::now() + window;
+        let mut pending = true;
+        if options.leading {
+            action().await;
+            pending = false;
+        }
+
+        loop {
+            match triggers.collect_until(deadline, None).await {
+                Some(triggered) => pending |= triggered,
+                None => {
+                    if options.trailing && options.flush_on_shutdown && pending {
+                        action().await;
+                    }
+                    return;
+                }
+            }
+
+            if !(options.trailing && pending) {
+                break;
+            }
+
+            // the trailing action starts a new window
+            deadline = Instant::now() + window;
+            action().await;
+            pending = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::Debouncer;
+    use super::Options;
+    use super::Throttler;
+    use crate::BlockzFutureExt;
+
+    /// Create an action that counts how many times it ran.
+    fn counter() -> (
+        Arc<AtomicUsize>,
+        impl FnMut() -> futures::future::Ready<()> + Send + 'static,
+    ) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let action_count = count.clone();
+        let action = move || {
+            action_count.fetch_add(1, Ordering::SeqCst);
+            futures::future::ready(())
+        };
+        (count, action)
+    }
+
+    #[tokio::test]
+    async fn test_debouncer_trailing() {
+        let (count, action) = counter();
+        let (debouncer, worker) = Debouncer::new(Duration::from_millis(20), Options::new(), action);
+        let worker = tokio::spawn(worker);
+
+        for _ in 0..5 {
+            assert!(debouncer.trigger());
+        }
+        crate::rt::sleep(Duration::from_millis(100)).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        drop(debouncer);
+        assert!(worker.await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_debouncer_leading() {
+        let (count, action) = counter();
+        let options = Options::new().leading(true).trailing(false);
+        let (debouncer, worker) = Debouncer::new(Duration::from_millis(500), options, action);
+        let worker = tokio::spawn(worker);
+
+        debouncer.trigger();
+        crate::rt::sleep(Duration::from_millis(20)).await;
+        debouncer.trigger();
+        debouncer.shutdown();
+
+        assert!(worker.await.is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_debouncer_flush_on_shutdown() {
+        let (count, action) = counter();
+        let options = Options::new().flush_on_shutdown(true);
+        let (debouncer, worker) = Debouncer::new(Duration::from_secs(60), options, action);
+
+        debouncer.trigger();
+        debouncer.shutdown();
+        assert!(!debouncer.trigger());
+
+        assert!(worker.timeout(Duration::from_millis(500)).await.is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_debouncer_shutdown_without_flush() {
+        let (count, action) = counter();
+        let (debouncer, worker) = Debouncer::new(Duration::from_secs(60), Options::new(), action);
+
+        debouncer.trigger();
+        drop(debouncer);
+
+        assert!(worker.timeout(Duration::from_millis(500)).await.is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_debouncer_worker_canceled() {
+        let (count, action) = counter();
+        let options = Options::new().flush_on_shutdown(true);
+        let (debouncer, worker) = Debouncer::new(Duration::from_secs(60), options, action);
+        let (worker, cancel) = worker.with_cancel_handle();
+        let worker = tokio::spawn(worker);
+
+        debouncer.trigger();
+        assert!(cancel.cancel());
+
+        assert!(matches!(worker.await, Ok(Err(_))));
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_throttler_leading_and_trailing() {
+        let (count, action) = counter();
+        let options = Options::new().leading(true);
+        let (throttler, worker) = Throttler::new(Duration::from_millis(50), options, action);
+        let worker = tokio::spawn(worker);
+
+        throttler.trigger();
+        crate::rt::sleep(Duration::from_millis(10)).await;
+        // the leading edge ran right away
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        throttler.trigger();
+        throttler.trigger();
+        crate::rt::sleep(Duration::from_millis(200)).await;
+        // the triggers within the window ran once, on the trailing edge
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        throttler.shutdown();
+        assert!(worker.await.is_ok());
+    }
+}
@@ -4,12 +4,17 @@ use std::future::Future;
 use std::time::Duration;
 use std::time::Instant;
 
-use tokio::sync::oneshot;
-
 use crate::cancel::Cancel;
 use crate::cancel::CancelChannelFuture;
 use crate::cancel::CancelHandle;
 use crate::flatten_interrupts::FlattenInterrupts;
+#[cfg(feature = "tracing")]
+use crate::instrument::InstrumentInterrupts;
+#[cfg(feature = "metrics")]
+use crate::measure::Measured;
+#[cfg(feature = "metrics")]
+use crate::measure::Outcome;
+use crate::rt::oneshot;
 use crate::timeout::Deadline;
 use crate::timeout::Timeout;
 
@@ -44,6 +49,27 @@ pub trait BlockzFutureExt: Future + Sized + private::Sealed {
         Timeout::new(self, timeout)
     }
 
+    /// Label the interrupts of this future with a name.
+    ///
+    /// The events emitted by the interrupt futures inside this future carry
+    /// the name in their `chain` field.
+    #[cfg(feature = "tracing")]
+    fn instrument_interrupts(self, name: &'static str) -> InstrumentInterrupts<Self> {
+        InstrumentInterrupts::new(self, name)
+    }
+
+    /// Record latency and outcome metrics for this future, labeled with a
+    /// name.
+    ///
+    /// See [`measure`](crate::measure) for the recorded metrics.
+    #[cfg(feature = "metrics")]
+    fn measured(self, name: &'static str) -> Measured<Self>
+    where
+        Self::Output: Outcome,
+    {
+        Measured::new(self, name)
+    }
+
     /// Flatten multiple interrupts into a single error.
     fn flatten_interrupts(self) -> FlattenInterrupts<Self> {
         todo!()
@@ -0,0 +1,37 @@
+//! Label chains of interrupts.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use crate::probe::chain;
+
+/// A future whose interrupts are labeled with a name.
+///
+/// The events emitted by the interrupt futures inside this future carry the
+/// name in their `chain` field.
+#[pin_project]
+pub struct InstrumentInterrupts<F> {
+    #[pin]
+    future: F,
+    name: &'static str,
+}
+
+impl<F: Future> InstrumentInterrupts<F> {
+    /// Create a new `InstrumentInterrupts` future.
+    pub fn new(future: F, name: &'static str) -> Self {
+        Self { future, name }
+    }
+}
+
+impl<F: Future> Future for InstrumentInterrupts<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let future: Pin<&mut F> = this.future;
+
+        chain::with(this.name, || future.poll(cx))
+    }
+}
@@ -0,0 +1,236 @@
+//! Join futures under a deadline, keeping partial results.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Instant;
+
+use crate::rt;
+use crate::rt::Sleep;
+
+/// The results of a [`JoinAllUntil`].
+#[derive(Debug)]
+pub struct PartialJoin<T, E> {
+    /// The results of the futures that completed, with their indices, in
+    /// input order.
+    pub completed: Vec<(usize, Result<T, E>)>,
+    /// The indices of the futures that did not complete before the deadline.
+    pub timed_out: Vec<usize>,
+    /// The indices of the futures that were canceled because enough of the
+    /// other futures succeeded.
+    pub canceled: Vec<usize>,
+}
+
+impl<T, E> PartialJoin<T, E> {
+    /// Returns whether every future completed.
+    pub fn is_complete(&self) -> bool {
+        self.timed_out.is_empty() && self.canceled.is_empty()
+    }
+
+    /// Get the number of futures that completed successfully.
+    pub fn successes(&self) -> usize {
+        self.completed.iter().filter(|(_, r)| r.is_ok()).count()
+    }
+}
+
+/// Join fallible futures until a deadline.
+///
+/// The returned future completes when every future completed or when the
+/// deadline is reached, whichever comes first. The futures that did not
+/// complete in time are dropped and reported as timed out.
+pub fn join_all_until<I, F, T, E>(deadline: Instant, futures: I) -> JoinAllUntil<F>
+where
+    I: IntoIterator<Item = F>,
+    F: Future<Output = Result<T, E>>,
+{
+    JoinAllUntil::new(deadline, futures)
+}
+
+/// A future that joins fallible futures until a deadline.
+#[pin_project]
+pub struct JoinAllUntil<F: Future> {
+    futures: Vec<Option<Pin<Box<F>>>>,
+    results: Vec<Option<F::Output>>,
+    #[pin]
+    sleep: Sleep,
+    min_successes: Option<usize>,
+    successes: usize,
+}
+
+impl<F, T, E> JoinAllUntil<F>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    /// Create a new `JoinAllUntil` future.
+    fn new<I: IntoIterator<Item = F>>(deadline: Instant, futures: I) -> Self {
+        let futures: Vec<_> = futures.into_iter().map(|f| Some(Box::pin(f))).collect();
+        let results = futures.iter().map(|_| None).collect();
+        Self {
+            futures,
+            results,
+            sleep: rt::sleep_until(deadline),
+            min_successes: None,
+            successes: 0,
+        }
+    }
+
+    /// Return early once `n` futures completed successfully.
+    ///
+    /// The futures that are still running at that point are dropped and
+    /// reported as canceled.
+    pub fn min_successes(mut self, n: usize) -> Self {
+        self.min_successes = Some(n);
+        self
+    }
+}
+
+impl<F, T, E> Future for JoinAllUntil<F>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = PartialJoin<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let futures: &mut Vec<Option<Pin<Box<F>>>> = this.futures;
+        let results: &mut Vec<Option<Result<T, E>>> = this.results;
+        let sleep: Pin<&mut Sleep> = this.sleep;
+
+        let mut pending = 0;
+        for (slot, result) in futures.iter_mut().zip(results.iter_mut()) {
+            if let Some(future) = slot {
+                if let Poll::Ready(out) = future.as_mut().poll(cx) {
+                    if out.is_ok() {
+                        *this.successes += 1;
+                    }
+                    *result = Some(out);
+                    *slot = None;
+                } else {
+                    pending += 1;
+                }
+            }
+        }
+
+        let quorum = matches!(this.min_successes, Some(n) if *this.successes >= *n);
+        let stragglers = if pending == 0 {
+            Straggler::None
+        } else if quorum {
+            Straggler::Canceled
+        } else if sleep.poll(cx).is_ready() {
+            Straggler::TimedOut
+        } else {
+            return Poll::Pending;
+        };
+
+        let mut join = PartialJoin {
+            completed: Vec::new(),
+            timed_out: Vec::new(),
+            canceled: Vec::new(),
+        };
+        for (index, (slot, result)) in futures.iter_mut().zip(results.iter_mut()).enumerate() {
+            if let Some(result) = result.take() {
+                join.completed.push((index, result));
+            } else if slot.take().is_some() {
+                match stragglers {
+                    Straggler::Canceled => join.canceled.push(index),
+                    _ => join.timed_out.push(index),
+                }
+            }
+        }
+        Poll::Ready(join)
+    }
+}
+
+/// Why the futures that are still running are dropped.
+enum Straggler {
+    None,
+    Canceled,
+    TimedOut,
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+
+    use super::join_all_until;
+    use crate::rt;
+
+    /// A future that sleeps for `ms` milliseconds before returning `result`.
+    fn after(
+        ms: u64,
+        result: Result<u64, &'static str>,
+    ) -> BoxFuture<'static, Result<u64, &'static str>> {
+        async move {
+            rt::sleep(Duration::from_millis(ms)).await;
+            result
+        }
+        .boxed()
+    }
+
+    #[tokio::test]
+    async fn test_join_all_until_complete() {
+        let deadline = Instant::now() + Duration::from_millis(500);
+        let join = join_all_until(deadline, vec![after(2, Ok(0)), after(1, Err("err"))]).await;
+
+        assert!(join.is_complete());
+        assert!(matches!(
+            join.completed.as_slice(),
+            [(0, Ok(0)), (1, Err("err"))]
+        ));
+        assert_eq!(join.successes(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_join_all_until_timed_out() {
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let join = join_all_until(
+            deadline,
+            vec![after(1, Ok(0)), after(1000, Ok(1)), after(2, Ok(2))],
+        )
+        .await;
+
+        assert!(!join.is_complete());
+        assert!(matches!(
+            join.completed.as_slice(),
+            [(0, Ok(0)), (2, Ok(2))]
+        ));
+        assert_eq!(join.timed_out, vec![1]);
+        assert!(join.canceled.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_join_all_until_min_successes() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let started = Instant::now();
+        let join = join_all_until(
+            deadline,
+            vec![
+                after(1, Err("err")),
+                after(1, Ok(1)),
+                after(1000, Ok(2)),
+                after(2, Ok(3)),
+            ],
+        )
+        .min_successes(2)
+        .await;
+
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(join.successes(), 2);
+        assert_eq!(join.canceled, vec![2]);
+        assert!(join.timed_out.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_join_all_until_empty() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let join = join_all_until(deadline, Vec::<BoxFuture<'static, Result<(), ()>>>::new()).await;
+
+        assert!(join.is_complete());
+        assert!(join.completed.is_empty());
+    }
+}
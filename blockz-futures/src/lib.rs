@@ -1,12 +1,33 @@
 //! Utilities for working with futures for the tokio stack.
+//!
+//! Timers and cancellation channels are provided by the runtime backend
+//! selected with cargo features, see [`rt`].
+//!
+//! With the `tracing` feature, interrupt futures enter the span they were
+//! created in while being polled and emit an event when they fire.
+//!
+//! With the `metrics` feature, futures can be measured to record their latency
+//! and outcome, see [`measure`].
 
 #[macro_use]
 extern crate pin_project;
 
 mod ext;
+mod probe;
 
 pub mod cancel;
+#[cfg(feature = "rt-tokio")]
+pub mod channel;
+pub mod debounce;
 pub mod flatten_interrupts;
+#[cfg(feature = "tracing")]
+pub mod instrument;
+pub mod join;
+#[cfg(feature = "metrics")]
+pub mod measure;
+pub mod rt;
+pub mod singleflight;
+pub mod sync;
 pub mod timeout;
 
 pub use self::ext::*;
@@ -22,13 +43,13 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_handle_future_dropped() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(2)).await;
         };
 
         let (cancel, cancel_handle) = fut.with_cancel_handle();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(1)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(1)) => {},
             _ = cancel => {
                 panic!("cancelable future completed before the sleep");
             },
@@ -42,13 +63,13 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_handle_future_completed() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
         };
 
         let (cancel, cancel_handle) = fut.with_cancel_handle();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(2)) => {
+            _ = crate::rt::sleep(std::time::Duration::from_millis(2)) => {
                 panic!("sleep completed before cancelable future");
             },
             _ = cancel => {},
@@ -61,7 +82,7 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_handle_cancel_ok() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(2)).await;
         };
 
         let (cancel, cancel_handle) = fut.with_cancel_handle();
@@ -70,7 +91,7 @@ mod test {
         let shared = cancel.shared();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(1)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(1)) => {},
             _ = shared.clone() => {
                 panic!("cancelable future completed before the sleep");
             },
@@ -85,7 +106,7 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_handle_ok() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
         };
 
         let (cancel, cancel_handle) = fut.with_cancel_handle();
@@ -94,7 +115,7 @@ mod test {
         let shared = cancel.shared();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(2)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(2)) => {},
             _ = shared.clone() => {},
         }
 
@@ -107,11 +128,11 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_future_cancel() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(2)).await;
         };
 
         let cancel = fut.with_cancel_future(async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
         });
 
         let result = cancel.await;
@@ -121,11 +142,11 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_future_ok() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
         };
 
         let cancel = fut.with_cancel_future(async {
-            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(2)).await;
         });
 
         let result = cancel.await;
@@ -135,10 +156,10 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_channel_cancel() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(2)).await;
         };
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
+        let (tx, rx) = crate::rt::oneshot::channel();
 
         let cancel = fut.with_cancel_channel(rx);
         // we create a shared future so that it doesn't get dropped when the
@@ -146,7 +167,7 @@ mod test {
         let shared = cancel.shared();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(1)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(1)) => {},
             _ = shared.clone() => {
                 panic!("cancelable future completed before the sleep");
             },
@@ -161,10 +182,10 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_with_cancel_channel_ok() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
         };
 
-        let (_tx, rx) = tokio::sync::oneshot::channel();
+        let (_tx, rx) = crate::rt::oneshot::channel();
 
         let cancel = fut.with_cancel_channel(rx);
 
@@ -175,7 +196,7 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_timeout() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(2)).await;
         };
 
         assert!(fut
@@ -187,7 +208,7 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_timeout_ok() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
         };
 
         assert!(fut
@@ -199,7 +220,7 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_deadline() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(3)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(3)).await;
         };
 
         assert!(fut
@@ -211,7 +232,7 @@ mod test {
     #[tokio::test]
     async fn test_blockz_future_ext_deadline_ok() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
         };
 
         assert!(fut
@@ -223,14 +244,14 @@ mod test {
     #[tokio::test]
     async fn test_interrupt_chain_timed_out() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(3)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(3)).await;
         };
 
         let (fut, cancel) = fut.timeout(Duration::from_millis(1)).with_cancel_handle();
         let fut = fut.shared();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(2)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(2)) => {},
             _ = fut.clone() => {},
         };
 
@@ -241,14 +262,14 @@ mod test {
     #[tokio::test]
     async fn test_interrupt_chain_canceled() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(3)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(3)).await;
         };
 
         let (fut, cancel) = fut.timeout(Duration::from_millis(2)).with_cancel_handle();
         let fut = fut.shared();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(1)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(1)) => {},
             _ = fut.clone() => {},
         };
 
@@ -259,7 +280,7 @@ mod test {
     #[tokio::test]
     async fn test_interrupt_chain_ok() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
             Ok::<_, &str>(())
         };
 
@@ -267,7 +288,7 @@ mod test {
         let fut = fut.shared();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(2)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(2)) => {},
             _ = fut.clone() => {},
         };
 
@@ -278,7 +299,7 @@ mod test {
     #[tokio::test]
     async fn test_interrupt_chain_err() {
         let fut = async {
-            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            crate::rt::sleep(std::time::Duration::from_millis(1)).await;
             Err::<(), _>("error")
         };
 
@@ -286,7 +307,7 @@ mod test {
         let fut = fut.shared();
 
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_millis(2)) => {},
+            _ = crate::rt::sleep(std::time::Duration::from_millis(2)) => {},
             _ = fut.clone() => {},
         };
 
@@ -0,0 +1,269 @@
+//! Record latency and outcome metrics for futures.
+//!
+//! A measured future records, labeled with its `name` and `outcome`:
+//!
+//! - the time between its first poll and its completion, in the
+//!   `blockz_futures_duration_seconds` histogram
+//! - its outcome, in the `blockz_futures_outcomes_total` counter
+//!
+//! The outcome is derived from the output of the future:
+//!
+//! - `success`: the future produced an `Ok`
+//! - `timeout` or `deadline`: the future produced an `Err` holding a
+//!   [`TimedOut`]
+//! - `cancel`: the future produced an `Err` holding a [`Canceled`]
+//! - `error`: the future produced any other `Err`
+//!
+//! Only the outermost result is inspected: the output of nested interrupts,
+//! e.g. `Ok(Err(TimedOut))` from a timeout wrapped in a cancel, is a success.
+//!
+//! A measured future that is dropped after being polled but before it
+//! completed is recorded as canceled.
+//!
+//! The metrics are sent to the recorder installed with the [`metrics`] crate.
+
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::cancel::Canceled;
+use crate::timeout::TimedOut;
+
+/// The name of the duration histogram.
+pub const DURATION_SECONDS: &str = "blockz_futures_duration_seconds";
+
+/// The name of the outcome counter.
+pub const OUTCOMES_TOTAL: &str = "blockz_futures_outcomes_total";
+
+/// The output of a future that can be measured.
+pub trait Outcome {
+    /// Get the name of the outcome of the output.
+    fn outcome(&self) -> &'static str;
+}
+
+impl<T, E: 'static> Outcome for Result<T, E> {
+    fn outcome(&self) -> &'static str {
+        let error: &dyn Any = match self {
+            Ok(_) => return "success",
+            Err(error) => error,
+        };
+        if let Some(timed_out) = error.downcast_ref::<TimedOut>() {
+            timed_out.kind()
+        } else if error.is::<Canceled>() {
+            "cancel"
+        } else {
+            "error"
+        }
+    }
+}
+
+/// A future that records metrics about its latency and outcome.
+#[pin_project(PinnedDrop)]
+pub struct Measured<F> {
+    #[pin]
+    future: F,
+    name: &'static str,
+    started: Option<Instant>,
+}
+
+impl<F: Future> Measured<F>
+where
+    F::Output: Outcome,
+{
+    /// Create a new `Measured` future.
+    pub fn new(future: F, name: &'static str) -> Self {
+        Self {
+            future,
+            name,
+            started: None,
+        }
+    }
+}
+
+impl<F: Future> Future for Measured<F>
+where
+    F::Output: Outcome,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let future: Pin<&mut F> = this.future;
+        let started: Instant = *this.started.get_or_insert_with(Instant::now);
+
+        if let Poll::Ready(out) = future.poll(cx) {
+            // the future is done, dropping it must not record anything
+            *this.started = None;
+            record(this.name, out.outcome(), started.elapsed());
+            Poll::Ready(out)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[pinned_drop]
+impl<F> PinnedDrop for Measured<F> {
+    fn drop(self: Pin<&mut Self>) {
+        let this = self.project();
+        if let Some(started) = this.started.take() {
+            record(this.name, "cancel", started.elapsed());
+        }
+    }
+}
+
+/// Record the duration and outcome of a measured future.
+fn record(name: &'static str, outcome: &'static str, elapsed: Duration) {
+    metrics::histogram!(DURATION_SECONDS, elapsed, "name" => name, "outcome" => outcome);
+    metrics::increment_counter!(OUTCOMES_TOTAL, "name" => name, "outcome" => outcome);
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+    use std::sync::Once;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use futures::FutureExt;
+    use metrics::GaugeValue;
+    use metrics::Key;
+    use metrics::Recorder;
+    use metrics::Unit;
+
+    use super::DURATION_SECONDS;
+    use super::OUTCOMES_TOTAL;
+    use crate::rt;
+    use crate::BlockzFutureExt;
+
+    /// The metrics recorded by every test, as (metric, name, outcome).
+    static RECORDED: Mutex<Vec<(String, String, String)>> = Mutex::new(Vec::new());
+
+    /// A recorder that remembers the histograms and counters it receives.
+    struct TestRecorder;
+
+    impl TestRecorder {
+        fn remember(key: &Key) {
+            let label = |wanted: &str| {
+                key.labels()
+                    .find(|label| label.key() == wanted)
+                    .map(|label| label.value().to_string())
+                    .unwrap_or_default()
+            };
+            RECORDED.lock().unwrap().push((
+                key.name().to_string(),
+                label("name"),
+                label("outcome"),
+            ));
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+        fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+        fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+        fn increment_counter(&self, key: &Key, _: u64) {
+            Self::remember(key);
+        }
+
+        fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+
+        fn record_histogram(&self, key: &Key, _: f64) {
+            Self::remember(key);
+        }
+    }
+
+    /// Install the test recorder, once for every test.
+    fn install() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| metrics::set_recorder(&TestRecorder).unwrap());
+    }
+
+    /// Get the outcomes recorded in `metric` for the future named `name`.
+    fn outcomes(metric: &str, name: &str) -> Vec<String> {
+        RECORDED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(m, n, _)| m == metric && n == name)
+            .map(|(_, _, outcome)| outcome.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_measured_success_and_error() {
+        install();
+
+        assert!(async { Ok::<_, ()>(()) }.measured("ok").await.is_ok());
+        assert!(async { Err::<(), _>(()) }.measured("err").await.is_err());
+
+        assert_eq!(outcomes(OUTCOMES_TOTAL, "ok"), vec!["success"]);
+        assert_eq!(outcomes(DURATION_SECONDS, "ok"), vec!["success"]);
+        assert_eq!(outcomes(OUTCOMES_TOTAL, "err"), vec!["error"]);
+    }
+
+    #[tokio::test]
+    async fn test_measured_timeout_and_deadline() {
+        install();
+
+        let fut = rt::sleep(Duration::from_millis(500))
+            .timeout(Duration::from_millis(1))
+            .measured("timeout");
+        assert!(fut.await.is_err());
+
+        let fut = rt::sleep(Duration::from_millis(500))
+            .deadline(Instant::now() + Duration::from_millis(1))
+            .measured("deadline");
+        assert!(fut.await.is_err());
+
+        assert_eq!(outcomes(OUTCOMES_TOTAL, "timeout"), vec!["timeout"]);
+        assert_eq!(outcomes(OUTCOMES_TOTAL, "deadline"), vec!["deadline"]);
+    }
+
+    #[tokio::test]
+    async fn test_measured_handled_timeout() {
+        install();
+
+        let fut = async {
+            rt::sleep(Duration::from_millis(500))
+                .timeout(Duration::from_millis(1))
+                .await
+                .or(Ok::<_, ()>(()))
+        };
+        assert!(fut.measured("handled").await.is_ok());
+
+        assert_eq!(outcomes(OUTCOMES_TOTAL, "handled"), vec!["success"]);
+    }
+
+    #[tokio::test]
+    async fn test_measured_cancel() {
+        install();
+
+        let fut = rt::sleep(Duration::from_millis(500))
+            .with_cancel_future(async {})
+            .measured("canceled");
+        assert!(fut.await.is_err());
+
+        let mut fut = Box::pin(
+            rt::sleep(Duration::from_millis(500))
+                .timeout(Duration::from_millis(500))
+                .measured("dropped"),
+        );
+        assert!((&mut fut).now_or_never().is_none());
+        drop(fut);
+
+        let fut = async { Ok::<_, ()>(()) }.measured("never-polled");
+        drop(fut);
+
+        assert_eq!(outcomes(OUTCOMES_TOTAL, "canceled"), vec!["cancel"]);
+        assert_eq!(outcomes(OUTCOMES_TOTAL, "dropped"), vec!["cancel"]);
+        assert!(outcomes(OUTCOMES_TOTAL, "never-polled").is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Observability hooks for interrupt futures.
+//!
+//! Every interrupt future holds a [`Probe`]. With the `tracing` feature, the
+//! probe captures the caller's span when the interrupt future is created,
+//! enters it while the future is polled and emits an event when the interrupt
+//! fires. Without it, the probe does nothing.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::cancel::CancelReason;
+
+/// An interrupt that fired.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Interrupt {
+    /// A timeout elapsed.
+    Timeout,
+    /// A deadline was reached.
+    Deadline,
+    /// A future was canceled.
+    Cancel(#[cfg_attr(not(feature = "tracing"), allow(dead_code))] CancelReason),
+}
+
+impl Interrupt {
+    /// Get the name of the kind of this interrupt.
+    #[cfg_attr(not(any(feature = "tracing", feature = "metrics")), allow(dead_code))]
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Deadline => "deadline",
+            Self::Cancel(_) => "cancel",
+        }
+    }
+}
+
+/// Observes an interrupt future.
+pub(crate) struct Probe {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    created: Instant,
+    #[cfg(feature = "tracing")]
+    limit: Option<Duration>,
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) type Entered<'a> = tracing::span::Entered<'a>;
+#[cfg(not(feature = "tracing"))]
+pub(crate) type Entered<'a> = std::marker::PhantomData<&'a ()>;
+
+impl Probe {
+    /// Create a probe for an interrupt future without a time limit.
+    pub(crate) fn new() -> Self {
+        Self::with_limit(None)
+    }
+
+    /// Create a probe for an interrupt future that must complete in `limit`.
+    pub(crate) fn timeout(limit: Duration) -> Self {
+        Self::with_limit(Some(limit))
+    }
+
+    /// Create a probe for an interrupt future that must complete before
+    /// `deadline`.
+    pub(crate) fn deadline(deadline: Instant) -> Self {
+        Self::with_limit(Some(deadline.saturating_duration_since(Instant::now())))
+    }
+
+    #[cfg(feature = "tracing")]
+    fn with_limit(limit: Option<Duration>) -> Self {
+        Self {
+            span: tracing::Span::current(),
+            created: Instant::now(),
+            limit,
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn with_limit(_limit: Option<Duration>) -> Self {
+        Self {}
+    }
+
+    /// Enter the span the interrupt future was created in.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn enter(&self) -> Entered<'_> {
+        self.span.enter()
+    }
+
+    /// Enter the span the interrupt future was created in.
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn enter(&self) -> Entered<'_> {
+        std::marker::PhantomData
+    }
+
+    /// Report that the interrupt fired.
+    pub(crate) fn fired(&self, interrupt: Interrupt) {
+        #[cfg(feature = "tracing")]
+        self.trace(interrupt);
+        #[cfg(not(feature = "tracing"))]
+        let _ = interrupt;
+    }
+
+    /// Emit the event for an interrupt that fired.
+    #[cfg(feature = "tracing")]
+    fn trace(&self, interrupt: Interrupt) {
+        let kind = interrupt.kind();
+        let elapsed = self.created.elapsed();
+        let chain = chain::current();
+
+        match interrupt {
+            Interrupt::Timeout | Interrupt::Deadline => {
+                tracing::warn!(
+                    kind,
+                    limit = ?self.limit,
+                    elapsed = ?elapsed,
+                    chain,
+                    "future interrupted"
+                );
+            }
+            Interrupt::Cancel(reason) => {
+                tracing::debug!(
+                    kind,
+                    elapsed = ?elapsed,
+                    reason = reason.as_str(),
+                    chain,
+                    "future interrupted"
+                );
+            }
+        }
+    }
+}
+
+/// The label of the interrupt chain that is being polled on this thread.
+#[cfg(feature = "tracing")]
+pub(crate) mod chain {
+    use std::cell::Cell;
+
+    thread_local! {
+        static CHAIN: Cell<Option<&'static str>> = const { Cell::new(None) };
+    }
+
+    /// Get the label of the chain that is being polled.
+    pub(crate) fn current() -> Option<&'static str> {
+        CHAIN.with(Cell::get)
+    }
+
+    /// Run `f` with `name` as the label of the chain that is being polled.
+    pub(crate) fn with<R>(name: &'static str, f: impl FnOnce() -> R) -> R {
+        /// Restores the previous label, even if `f` panics.
+        struct Restore(Option<&'static str>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CHAIN.with(|chain| chain.set(self.0));
+            }
+        }
+
+        let _restore = Restore(CHAIN.with(|chain| chain.replace(Some(name))));
+        f()
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod test {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use tracing::field::Field;
+    use tracing::field::Visit;
+    use tracing::span;
+    use tracing::subscriber::Subscriber;
+    use tracing::Event;
+    use tracing::Metadata;
+
+    use crate::rt;
+    use crate::BlockzFutureExt;
+
+    /// The fields of a recorded event.
+    #[derive(Default)]
+    struct Fields(Vec<(String, String)>);
+
+    /// A subscriber that records the fields of every event.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Fields>>>);
+
+    impl Recorder {
+        /// Get the value of `field` in every recorded event.
+        fn values(&self, field: &str) -> Vec<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|fields| fields.0.iter().find(|(name, _)| name == field))
+                .map(|(_, value)| value.clone())
+                .collect()
+        }
+    }
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields);
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_probe_timeout_event() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let fut = rt::sleep(Duration::from_millis(500)).timeout(Duration::from_millis(1));
+        assert!(fut.await.is_err());
+
+        assert_eq!(recorder.values("kind"), vec!["timeout"]);
+        assert_eq!(recorder.values("limit"), vec!["Some(1ms)"]);
+        assert_eq!(recorder.values("chain"), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn test_probe_cancel_event_in_chain() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let (fut, cancel) = rt::sleep(Duration::from_millis(500))
+            .timeout(Duration::from_millis(500))
+            .with_cancel_handle();
+        drop(cancel);
+        assert!(fut.instrument_interrupts("sleepy").await.is_err());
+
+        assert_eq!(recorder.values("kind"), vec!["cancel"]);
+        assert_eq!(recorder.values("reason"), vec!["dropped"]);
+        assert_eq!(recorder.values("chain"), vec!["sleepy"]);
+    }
+
+    #[tokio::test]
+    async fn test_probe_no_event_when_completed() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let fut = async {}.deadline(std::time::Instant::now() + Duration::from_millis(500));
+        assert!(fut.await.is_ok());
+
+        assert!(recorder.values("kind").is_empty());
+    }
+}
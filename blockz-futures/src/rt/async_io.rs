@@ -0,0 +1,75 @@
+//! async-io runtime backend.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_io::Timer;
+
+pub use futures::channel::oneshot;
+
+/// A future that completes after a point in time.
+pub struct Sleep(Timer);
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let timer: Pin<&mut Timer> = Pin::new(&mut self.get_mut().0);
+        timer.poll(cx).map(|_| ())
+    }
+}
+
+/// Wait until `duration` has elapsed.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep(Timer::after(duration))
+}
+
+/// Wait until `deadline` is reached.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep(Timer::at(deadline))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use futures::executor::block_on;
+
+    use super::sleep;
+    use super::sleep_until;
+    use crate::cancel::CancelReason;
+    use crate::BlockzFutureExt;
+
+    #[test]
+    fn test_async_io_sleep() {
+        let start = Instant::now();
+        block_on(sleep(Duration::from_millis(5)));
+        assert!(start.elapsed() >= Duration::from_millis(5));
+
+        let deadline = Instant::now() + Duration::from_millis(5);
+        block_on(sleep_until(deadline));
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn test_async_io_timeout() {
+        let fut = sleep(Duration::from_secs(5)).timeout(Duration::from_millis(1));
+        assert!(block_on(fut).is_err());
+
+        let fut = async { 42 }.timeout(Duration::from_secs(5));
+        assert_eq!(block_on(fut).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_async_io_cancel() {
+        let (fut, handle) = sleep(Duration::from_secs(5)).with_cancel_handle();
+        assert!(handle.cancel());
+        let err = block_on(fut).expect_err("future should have been canceled");
+        assert_eq!(err.reason(), CancelReason::Signaled);
+    }
+}
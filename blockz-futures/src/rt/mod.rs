@@ -0,0 +1,23 @@
+//! Runtime backend used for timers and cancellation channels.
+//!
+//! The backend is selected with cargo features:
+//!
+//! - `rt-tokio` (default): timers from `tokio::time` and channels from
+//!   `tokio::sync`.
+//! - `rt-async-io`: timers from `async-io` and channels from
+//!   `futures::channel`, for use with smol/async-executor.
+//!
+//! If both features are enabled, the tokio backend is used.
+
+#[cfg(not(any(feature = "rt-tokio", feature = "rt-async-io")))]
+compile_error!("blockz-futures requires one of the `rt-tokio` or `rt-async-io` features");
+
+#[cfg(all(feature = "rt-async-io", not(feature = "rt-tokio")))]
+mod async_io;
+#[cfg(feature = "rt-tokio")]
+mod tokio;
+
+#[cfg(all(feature = "rt-async-io", not(feature = "rt-tokio")))]
+pub use self::async_io::*;
+#[cfg(feature = "rt-tokio")]
+pub use self::tokio::*;
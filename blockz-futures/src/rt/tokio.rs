@@ -0,0 +1,34 @@
+//! Tokio runtime backend.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+pub use tokio::sync::oneshot;
+
+/// A future that completes after a point in time.
+#[pin_project]
+pub struct Sleep(#[pin] tokio::time::Sleep);
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let sleep: Pin<&mut tokio::time::Sleep> = this.0;
+        sleep.poll(cx)
+    }
+}
+
+/// Wait until `duration` has elapsed.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep(tokio::time::sleep(duration))
+}
+
+/// Wait until `deadline` is reached.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep(tokio::time::sleep_until(deadline.into()))
+}
@@ -0,0 +1,356 @@
+//! Deduplicate concurrent identical async calls.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::sync::Weak;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::future::BoxFuture;
+use futures::future::Shared;
+use futures::future::WeakShared;
+use futures::FutureExt;
+
+/// The shared computation of a flight.
+type SharedCall<V> = Shared<BoxFuture<'static, V>>;
+
+/// Calls that are currently in flight, by key.
+struct Calls<K, V> {
+    next_id: u64,
+    calls: HashMap<K, Call<V>>,
+}
+
+/// A call that is in flight.
+struct Call<V> {
+    id: u64,
+    future: WeakShared<BoxFuture<'static, V>>,
+}
+
+/// Deduplicates concurrent calls that share the same key.
+///
+/// Concurrent calls with the same key share a single in-flight future and all
+/// of them receive a clone of its result. Every caller gets its own [`Flight`],
+/// so each one can apply its own timeout or cancellation: dropping a `Flight`
+/// does not abort the shared computation for the other callers. The shared
+/// computation is dropped only when every `Flight` waiting on it has been
+/// dropped.
+///
+/// Once a computation completes, the next call with the same key starts a new
+/// one - results are not cached.
+pub struct SingleFlight<K, V> {
+    inner: Arc<Mutex<Calls<K, V>>>,
+}
+
+impl<K, V> SingleFlight<K, V>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Create a new `SingleFlight`.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Calls {
+                next_id: 0,
+                calls: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Join the in-flight call for `key` or start a new one with `f`.
+    ///
+    /// `f` is only called if there is no call in flight for `key`. It is
+    /// called without holding any lock, so it may call back into this
+    /// `SingleFlight`. If another call for `key` starts while `f` runs, the
+    /// future returned by `f` is dropped without being polled and that call is
+    /// joined instead.
+    pub fn call<F, Fut>(&self, key: K, f: F) -> Flight<K, V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        if let Some(flight) = self.join(&lock(&self.inner), &key) {
+            return flight;
+        }
+
+        let future = f();
+
+        let mut calls = lock(&self.inner);
+        if let Some(flight) = self.join(&calls, &key) {
+            return flight;
+        }
+
+        let id = calls.next_id;
+        calls.next_id = calls.next_id.wrapping_add(1);
+
+        let inner = Arc::downgrade(&self.inner);
+        let forget_key = key.clone();
+        let future = async move {
+            let out = future.await;
+            // the call is done, later calls must start a new one
+            forget(&inner, &forget_key, id);
+            out
+        }
+        .boxed()
+        .shared();
+
+        if let Some(weak) = future.downgrade() {
+            calls.calls.insert(key.clone(), Call { id, future: weak });
+        }
+
+        Flight::new(key, id, future, Arc::downgrade(&self.inner))
+    }
+
+    /// Join the call in flight for `key`, if there is one.
+    fn join(&self, calls: &Calls<K, V>, key: &K) -> Option<Flight<K, V>> {
+        let call = calls.calls.get(key)?;
+        let future = call.future.upgrade()?;
+        Some(Flight::new(
+            key.clone(),
+            call.id,
+            future,
+            Arc::downgrade(&self.inner),
+        ))
+    }
+
+    /// Get the number of calls that are currently in flight.
+    pub fn in_flight(&self) -> usize {
+        lock(&self.inner).calls.len()
+    }
+}
+
+impl<K, V> Default for SingleFlight<K, V>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Clone for SingleFlight<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// A caller's handle to a shared in-flight call.
+///
+/// Dropping a `Flight` only gives up on the result for this caller. The shared
+/// computation is dropped when the last `Flight` waiting on it is dropped.
+pub struct Flight<K: Eq + Hash, V> {
+    key: Option<K>,
+    id: u64,
+    future: SharedCall<V>,
+    calls: Weak<Mutex<Calls<K, V>>>,
+}
+
+impl<K: Eq + Hash, V> Flight<K, V> {
+    /// Create a new `Flight`.
+    fn new(key: K, id: u64, future: SharedCall<V>, calls: Weak<Mutex<Calls<K, V>>>) -> Self {
+        Self {
+            key: Some(key),
+            id,
+            future,
+            calls,
+        }
+    }
+}
+
+// the key is never pinned and the shared future is `Unpin`
+impl<K: Eq + Hash, V> Unpin for Flight<K, V> {}
+
+impl<K: Eq + Hash, V: Clone> Future for Flight<K, V> {
+    type Output = V;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let future: Pin<&mut SharedCall<V>> = Pin::new(&mut this.future);
+
+        if let Poll::Ready(out) = future.poll(cx) {
+            // the call is done and has already been forgotten
+            this.key = None;
+            Poll::Ready(out)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> Drop for Flight<K, V> {
+    fn drop(&mut self) {
+        let key = match self.key.take() {
+            Some(key) => key,
+            None => return,
+        };
+        let calls = match self.calls.upgrade() {
+            Some(calls) => calls,
+            None => return,
+        };
+
+        let mut calls = lock(&calls);
+        // the count is checked while holding the lock, so no other caller can
+        // join this call in the meantime
+        if self.future.strong_count() == Some(1) {
+            if let Some(call) = calls.calls.get(&key) {
+                if call.id == self.id {
+                    calls.calls.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// Forget the call with `id` for `key`, if it is still registered.
+fn forget<K: Eq + Hash, V>(calls: &Weak<Mutex<Calls<K, V>>>, key: &K, id: u64) {
+    if let Some(calls) = calls.upgrade() {
+        let mut calls = lock(&calls);
+        if let Some(call) = calls.calls.get(key) {
+            if call.id == id {
+                calls.calls.remove(key);
+            }
+        }
+    }
+}
+
+/// Lock the calls, ignoring poisoning.
+fn lock<K, V>(calls: &Mutex<Calls<K, V>>) -> MutexGuard<'_, Calls<K, V>> {
+    calls.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::SingleFlight;
+    use crate::BlockzFutureExt;
+
+    /// Sets a flag when dropped.
+    struct DropFlag(Arc<AtomicUsize>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_singleflight_deduplicates_calls() {
+        let flight = SingleFlight::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let call = || {
+            let calls = calls.clone();
+            flight.call("key", move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                crate::rt::sleep(Duration::from_millis(100)).await;
+                42
+            })
+        };
+
+        let (a, b, c) = tokio::join!(call(), call(), call());
+
+        assert_eq!((a, b, c), (42, 42, 42));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(flight.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_singleflight_distinct_keys() {
+        let flight = SingleFlight::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let call = |key| {
+            let calls = calls.clone();
+            flight.call(key, move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                key
+            })
+        };
+
+        assert_eq!(tokio::join!(call(1), call(2)), (1, 2));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_singleflight_waiter_timeout_does_not_abort() {
+        let flight = SingleFlight::new();
+
+        let slow = flight.call("key", || async {
+            crate::rt::sleep(Duration::from_millis(100)).await;
+            "done"
+        });
+        let impatient = flight
+            .call("key", || async { unreachable!() })
+            .timeout(Duration::from_millis(1));
+
+        let (slow, impatient) = tokio::join!(slow, impatient);
+
+        assert!(impatient.is_err());
+        assert_eq!(slow, "done");
+    }
+
+    #[tokio::test]
+    async fn test_singleflight_all_waiters_dropped() {
+        let flight = SingleFlight::new();
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let guard = DropFlag(dropped.clone());
+        let first = flight.call("key", move || async move {
+            let _guard = guard;
+            crate::rt::sleep(Duration::from_millis(100)).await;
+        });
+        let second = flight.call("key", || async { unreachable!() });
+
+        assert!(first.timeout(Duration::from_millis(1)).await.is_err());
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+
+        drop(second);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        assert_eq!(flight.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_singleflight_reentrant_call() {
+        let flight = SingleFlight::new();
+
+        let inner = flight.clone();
+        let out = flight
+            .call("outer", move || {
+                let inner = inner.call("inner", || async { 1 });
+                async move { inner.await + 1 }
+            })
+            .await;
+
+        assert_eq!(out, 2);
+        assert_eq!(flight.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_singleflight_completed_calls_are_not_cached() {
+        let flight = SingleFlight::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..2 {
+            let calls = calls.clone();
+            flight
+                .call("key", move || async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                })
+                .await;
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,221 @@
+//! Wait for a number of tasks to reach the same point.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::lock;
+use super::Outstanding;
+use super::WaitTimeout;
+use super::Waiters;
+use crate::timeout::Deadline;
+use crate::timeout::Timeout;
+use crate::BlockzFutureExt;
+
+/// A reusable barrier for a fixed number of participants.
+///
+/// Waiters complete once `participants` of them are waiting at the same time.
+/// The barrier is then reset and can be used again. A waiter that is dropped
+/// before the barrier is released, for example because it timed out or was
+/// canceled, no longer counts as arrived.
+#[derive(Clone)]
+pub struct Barrier {
+    inner: Arc<BarrierInner>,
+}
+
+struct BarrierInner {
+    participants: usize,
+    state: Mutex<BarrierState>,
+}
+
+struct BarrierState {
+    arrived: usize,
+    /// Incremented every time the barrier is released.
+    generation: u64,
+    waiters: Waiters,
+}
+
+impl Barrier {
+    /// Create a new `Barrier` for `participants` tasks.
+    ///
+    /// A barrier for zero participants behaves like a barrier for one.
+    pub fn new(participants: usize) -> Self {
+        Self {
+            inner: Arc::new(BarrierInner {
+                participants: participants.max(1),
+                state: Mutex::new(BarrierState {
+                    arrived: 0,
+                    generation: 0,
+                    waiters: Waiters::default(),
+                }),
+            }),
+        }
+    }
+
+    /// Get the number of participants that have not arrived at the barrier
+    /// yet.
+    pub fn outstanding(&self) -> usize {
+        self.inner.outstanding()
+    }
+
+    /// Wait for all participants to arrive at the barrier.
+    ///
+    /// The participant arrives when the returned future is first polled.
+    pub fn wait(&self) -> BarrierWait {
+        BarrierWait {
+            barrier: self.inner.clone(),
+            generation: None,
+            released: false,
+            key: None,
+        }
+    }
+
+    /// Wait for all participants to arrive at the barrier in a time interval.
+    pub fn wait_timeout(&self, timeout: Duration) -> WaitTimeout<Timeout<BarrierWait>> {
+        WaitTimeout::new(self.wait().timeout(timeout), self.inner.clone())
+    }
+
+    /// Wait for all participants to arrive at the barrier before a point in
+    /// time.
+    pub fn wait_deadline(&self, deadline: Instant) -> WaitTimeout<Deadline<BarrierWait>> {
+        WaitTimeout::new(self.wait().deadline(deadline), self.inner.clone())
+    }
+}
+
+impl Outstanding for BarrierInner {
+    fn outstanding(&self) -> usize {
+        self.participants - lock(&self.state).arrived
+    }
+}
+
+/// The result of waiting at a [`Barrier`].
+#[derive(Clone, Copy, Debug)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns whether this participant released the barrier.
+    ///
+    /// Exactly one participant is the leader every time the barrier is
+    /// released.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+/// Future that completes when all participants arrived at a [`Barrier`].
+pub struct BarrierWait {
+    barrier: Arc<BarrierInner>,
+    /// The generation this participant arrived in.
+    generation: Option<u64>,
+    released: bool,
+    key: Option<u64>,
+}
+
+impl Future for BarrierWait {
+    type Output = BarrierWaitResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = lock(&this.barrier.state);
+
+        match this.generation {
+            Some(generation) if generation != state.generation => {
+                this.released = true;
+                Poll::Ready(BarrierWaitResult(false))
+            }
+            Some(_) => {
+                state.waiters.register(&mut this.key, cx.waker());
+                Poll::Pending
+            }
+            None if state.arrived + 1 == this.barrier.participants => {
+                state.arrived = 0;
+                state.generation = state.generation.wrapping_add(1);
+                state.waiters.wake_all();
+                this.released = true;
+                Poll::Ready(BarrierWaitResult(true))
+            }
+            None => {
+                state.arrived += 1;
+                this.generation = Some(state.generation);
+                state.waiters.register(&mut this.key, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for BarrierWait {
+    fn drop(&mut self) {
+        let mut state = lock(&self.barrier.state);
+        state.waiters.remove(self.key);
+        if self.released {
+            return;
+        }
+        if let Some(generation) = self.generation {
+            // the participant gave up before the barrier was released
+            if state.generation == generation {
+                state.arrived -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::Barrier;
+    use crate::sync::lock;
+
+    #[tokio::test]
+    async fn test_barrier_ok() {
+        let barrier = Barrier::new(3);
+
+        let (a, b, c) = tokio::join!(barrier.wait(), barrier.wait(), barrier.wait());
+
+        let leaders = [a, b, c].iter().filter(|r| r.is_leader()).count();
+        assert_eq!(leaders, 1);
+        assert_eq!(barrier.outstanding(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_barrier_reusable() {
+        let barrier = Barrier::new(2);
+
+        for _ in 0..3 {
+            let (a, b) = tokio::join!(barrier.wait(), barrier.wait());
+            assert!(a.is_leader() ^ b.is_leader());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_barrier_timed_out() {
+        let barrier = Barrier::new(3);
+
+        let err = barrier
+            .wait_timeout(Duration::from_millis(1))
+            .await
+            .expect_err("barrier should have timed out");
+        assert_eq!(err.outstanding(), 2);
+        // the participant that timed out is not counted as arrived anymore
+        assert_eq!(barrier.outstanding(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_barrier_timed_out_waiters_removed() {
+        let barrier = Barrier::new(2);
+
+        for _ in 0..10 {
+            assert!(barrier
+                .wait_timeout(Duration::from_millis(1))
+                .await
+                .is_err());
+        }
+        assert!(lock(&barrier.inner.state).waiters.wakers.is_empty());
+    }
+}
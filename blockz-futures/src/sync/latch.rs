@@ -0,0 +1,103 @@
+//! Wait for a number of events to happen.
+
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::Counter;
+use super::Outstanding;
+use super::Wait;
+use super::WaitTimeout;
+use crate::timeout::Deadline;
+use crate::timeout::Timeout;
+use crate::BlockzFutureExt;
+
+/// Waits for a fixed number of events to happen.
+///
+/// The latch starts with a count that is decremented by
+/// [`CountDownLatch::count_down`]. Waiters complete once the count reaches
+/// zero. Unlike a [`WaitGroup`](super::WaitGroup), a latch cannot be reset.
+#[derive(Clone)]
+pub struct CountDownLatch {
+    counter: Arc<Counter>,
+}
+
+impl CountDownLatch {
+    /// Create a new `CountDownLatch` that waits for `count` events.
+    pub fn new(count: usize) -> Self {
+        Self {
+            counter: Arc::new(Counter::new(count)),
+        }
+    }
+
+    /// Count an event.
+    ///
+    /// Counting down a latch that already reached zero has no effect.
+    pub fn count_down(&self) {
+        self.counter.decrement();
+    }
+
+    /// Get the number of events that have not happened yet.
+    pub fn count(&self) -> usize {
+        self.counter.outstanding()
+    }
+
+    /// Wait for the count to reach zero.
+    pub fn wait(&self) -> Wait {
+        Wait::new(self.counter.clone())
+    }
+
+    /// Wait for the count to reach zero in a time interval.
+    pub fn wait_timeout(&self, timeout: Duration) -> WaitTimeout<Timeout<Wait>> {
+        WaitTimeout::new(self.wait().timeout(timeout), self.counter.clone())
+    }
+
+    /// Wait for the count to reach zero before a point in time.
+    pub fn wait_deadline(&self, deadline: Instant) -> WaitTimeout<Deadline<Wait>> {
+        WaitTimeout::new(self.wait().deadline(deadline), self.counter.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use super::CountDownLatch;
+    use crate::BlockzFutureExt;
+
+    #[tokio::test]
+    async fn test_latch_ok() {
+        let latch = CountDownLatch::new(2);
+
+        let waiter = tokio::spawn(latch.wait().timeout(Duration::from_millis(500)));
+        latch.count_down();
+        latch.count_down();
+        // counting down past zero has no effect
+        latch.count_down();
+
+        assert!(matches!(waiter.await, Ok(Ok(()))));
+        assert_eq!(latch.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_latch_deadline() {
+        let latch = CountDownLatch::new(3);
+        latch.count_down();
+
+        let err = latch
+            .wait_deadline(Instant::now() + Duration::from_millis(1))
+            .await
+            .expect_err("latch should have timed out");
+        assert_eq!(err.outstanding(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_latch_cancel() {
+        let latch = CountDownLatch::new(1);
+
+        let (wait, cancel) = latch.wait().with_cancel_handle();
+        assert!(cancel.cancel());
+        assert!(wait.await.is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Async coordination primitives.
+//!
+//! The wait operations of these primitives are futures, so they can be
+//! combined with the [`BlockzFutureExt`](crate::BlockzFutureExt) interrupts.
+//! Each primitive also provides `wait_timeout` and `wait_deadline`, which
+//! report how many participants were still outstanding when time ran out.
+
+mod barrier;
+mod latch;
+mod wait_group;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+
+use thiserror::Error;
+
+use crate::timeout::TimedOut;
+
+pub use self::barrier::*;
+pub use self::latch::*;
+pub use self::wait_group::*;
+
+/// Error type for waits that ran out of time.
+#[derive(Clone, Copy, Debug, Error)]
+#[error("wait timed out with {outstanding} participant(s) outstanding")]
+pub struct WaitTimedOut {
+    outstanding: usize,
+}
+
+impl WaitTimedOut {
+    /// Get the number of participants that were still outstanding when the
+    /// wait timed out.
+    ///
+    /// This is a snapshot taken at the timeout: for a barrier, it still counts
+    /// the participant that timed out as arrived.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding
+    }
+}
+
+/// Primitives that can report how many participants are outstanding.
+trait Outstanding {
+    /// Get the number of outstanding participants.
+    fn outstanding(&self) -> usize;
+}
+
+/// A wait that must complete in time.
+///
+/// When time runs out, the error reports how many participants were still
+/// outstanding.
+#[pin_project]
+pub struct WaitTimeout<F> {
+    #[pin]
+    future: F,
+    primitive: Arc<dyn Outstanding + Send + Sync>,
+}
+
+impl<F> WaitTimeout<F> {
+    /// Create a new `WaitTimeout` future.
+    fn new(future: F, primitive: Arc<dyn Outstanding + Send + Sync>) -> Self {
+        Self { future, primitive }
+    }
+}
+
+impl<F, T> Future for WaitTimeout<F>
+where
+    F: Future<Output = Result<T, TimedOut>>,
+{
+    type Output = Result<T, WaitTimedOut>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let future: Pin<&mut F> = this.future;
+        let primitive = this.primitive;
+
+        if let Poll::Ready(result) = future.poll(cx) {
+            Poll::Ready(result.map_err(|_| WaitTimedOut {
+                outstanding: primitive.outstanding(),
+            }))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Wakers of the tasks waiting on a primitive, keyed by waiter.
+///
+/// A waiter removes its waker when it is dropped, so waiters that give up,
+/// for example because they timed out, do not accumulate.
+#[derive(Default)]
+struct Waiters {
+    wakers: Vec<(u64, Waker)>,
+    next_key: u64,
+}
+
+impl Waiters {
+    /// Register the waker of a waiter to be woken by `wake_all`.
+    ///
+    /// `key` identifies the waiter; it is assigned on the first registration.
+    fn register(&mut self, key: &mut Option<u64>, waker: &Waker) {
+        let key = *key.get_or_insert_with(|| {
+            self.next_key = self.next_key.wrapping_add(1);
+            self.next_key
+        });
+        match self.wakers.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, w)) if w.will_wake(waker) => {}
+            Some((_, w)) => *w = waker.clone(),
+            None => self.wakers.push((key, waker.clone())),
+        }
+    }
+
+    /// Remove the waker of a waiter, if it is registered.
+    fn remove(&mut self, key: Option<u64>) {
+        if let Some(key) = key {
+            self.wakers.retain(|(k, _)| *k != key);
+        }
+    }
+
+    /// Wake all registered wakers.
+    fn wake_all(&mut self) {
+        self.wakers.drain(..).for_each(|(_, waker)| waker.wake());
+    }
+}
+
+/// Lock a primitive's state, ignoring poisoning.
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A counter that wakes its waiters when it reaches zero.
+///
+/// This is the shared core of [`WaitGroup`] and [`CountDownLatch`].
+struct Counter(Mutex<CounterState>);
+
+struct CounterState {
+    count: usize,
+    /// Incremented every time the count reaches zero.
+    epoch: u64,
+    waiters: Waiters,
+}
+
+impl Counter {
+    /// Create a new counter.
+    fn new(count: usize) -> Self {
+        Self(Mutex::new(CounterState {
+            count,
+            epoch: 0,
+            waiters: Waiters::default(),
+        }))
+    }
+
+    /// Increment the counter.
+    fn increment(&self, n: usize) {
+        lock(&self.0).count += n;
+    }
+
+    /// Decrement the counter.
+    ///
+    /// Returns false if the counter was already zero.
+    fn decrement(&self) -> bool {
+        let mut state = lock(&self.0);
+        match state.count {
+            0 => false,
+            1 => {
+                state.count = 0;
+                state.epoch = state.epoch.wrapping_add(1);
+                state.waiters.wake_all();
+                true
+            }
+            _ => {
+                state.count -= 1;
+                true
+            }
+        }
+    }
+}
+
+impl Outstanding for Counter {
+    fn outstanding(&self) -> usize {
+        lock(&self.0).count
+    }
+}
+
+/// Future that completes when a [`WaitGroup`] or a [`CountDownLatch`] reaches
+/// zero.
+pub struct Wait {
+    counter: Arc<Counter>,
+    epoch: Option<u64>,
+    key: Option<u64>,
+}
+
+impl Wait {
+    /// Create a new `Wait` future.
+    fn new(counter: Arc<Counter>) -> Self {
+        Self {
+            counter,
+            epoch: None,
+            key: None,
+        }
+    }
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = lock(&this.counter.0);
+
+        if state.count == 0 {
+            return Poll::Ready(());
+        }
+
+        match this.epoch {
+            // the counter reached zero since we started waiting, even if it
+            // has been incremented again in the meantime
+            Some(epoch) if epoch != state.epoch => Poll::Ready(()),
+            _ => {
+                this.epoch = Some(state.epoch);
+                state.waiters.register(&mut this.key, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Wait {
+    fn drop(&mut self) {
+        lock(&self.counter.0).waiters.remove(self.key);
+    }
+}
@@ -0,0 +1,138 @@
+//! Wait for a group of tasks to finish.
+
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::Counter;
+use super::Outstanding;
+use super::Wait;
+use super::WaitTimeout;
+use crate::timeout::Deadline;
+use crate::timeout::Timeout;
+use crate::BlockzFutureExt;
+
+/// Waits for a group of tasks to finish.
+///
+/// Tasks are added to the group with [`WaitGroup::add`] and mark themselves as
+/// finished with [`WaitGroup::done`]. Waiters complete once every task that has
+/// been added has finished. A `WaitGroup` can be reused after it reaches zero.
+#[derive(Clone)]
+pub struct WaitGroup {
+    counter: Arc<Counter>,
+}
+
+impl WaitGroup {
+    /// Create a new, empty `WaitGroup`.
+    pub fn new() -> Self {
+        Self {
+            counter: Arc::new(Counter::new(0)),
+        }
+    }
+
+    /// Add `n` tasks to the group.
+    pub fn add(&self, n: usize) {
+        self.counter.increment(n);
+    }
+
+    /// Mark a task as finished.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if it's called more times than tasks have been
+    /// added to the group.
+    pub fn done(&self) {
+        if !self.counter.decrement() {
+            panic!("WaitGroup::done called more times than tasks were added");
+        }
+    }
+
+    /// Get the number of tasks that have not finished yet.
+    pub fn outstanding(&self) -> usize {
+        self.counter.outstanding()
+    }
+
+    /// Wait for every task in the group to finish.
+    pub fn wait(&self) -> Wait {
+        Wait::new(self.counter.clone())
+    }
+
+    /// Wait for every task in the group to finish in a time interval.
+    pub fn wait_timeout(&self, timeout: Duration) -> WaitTimeout<Timeout<Wait>> {
+        WaitTimeout::new(self.wait().timeout(timeout), self.counter.clone())
+    }
+
+    /// Wait for every task in the group to finish before a point in time.
+    pub fn wait_deadline(&self, deadline: Instant) -> WaitTimeout<Deadline<Wait>> {
+        WaitTimeout::new(self.wait().deadline(deadline), self.counter.clone())
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::WaitGroup;
+    use crate::sync::lock;
+    use crate::BlockzFutureExt;
+
+    #[tokio::test]
+    async fn test_wait_group_empty() {
+        let wg = WaitGroup::new();
+        assert!(wg.wait().timeout(Duration::from_millis(1)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_group_ok() {
+        let wg = WaitGroup::new();
+        wg.add(3);
+
+        for i in 0..3 {
+            let wg = wg.clone();
+            tokio::spawn(async move {
+                crate::rt::sleep(Duration::from_millis(i)).await;
+                wg.done();
+            });
+        }
+
+        assert!(wg.wait_timeout(Duration::from_millis(500)).await.is_ok());
+        assert_eq!(wg.outstanding(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_group_timed_out() {
+        let wg = WaitGroup::new();
+        wg.add(2);
+        wg.done();
+
+        let err = wg
+            .wait_timeout(Duration::from_millis(1))
+            .await
+            .expect_err("wait group should have timed out");
+        assert_eq!(err.outstanding(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_group_timed_out_waiters_removed() {
+        let wg = WaitGroup::new();
+        wg.add(1);
+
+        for _ in 0..10 {
+            assert!(wg.wait_timeout(Duration::from_millis(1)).await.is_err());
+        }
+        assert!(lock(&wg.counter.0).waiters.wakers.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wait_group_done_too_many_times() {
+        let wg = WaitGroup::new();
+        wg.done();
+    }
+}
@@ -9,23 +9,42 @@ use std::time::Instant;
 
 use thiserror::Error;
 
+use crate::probe::Interrupt;
+use crate::probe::Probe;
+use crate::rt;
+use crate::rt::Sleep;
+
 /// Error type for futures that ran out of time.
 #[derive(Clone, Copy, Debug, Error)]
 #[error("future timed out")]
-pub struct TimedOut(());
+pub struct TimedOut(Interrupt);
+
+impl TimedOut {
+    /// Get the name of the kind of interrupt that fired: `timeout` or
+    /// `deadline`.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn kind(&self) -> &'static str {
+        self.0.kind()
+    }
+}
 
 /// A future that must complete in a certain time interval.
 #[pin_project]
 pub struct Timeout<F> {
     #[pin]
-    future: tokio::time::Timeout<F>,
+    future: F,
+    #[pin]
+    sleep: Sleep,
+    probe: Probe,
 }
 
 impl<F: Future> Timeout<F> {
     /// Create a new `Timeout` future.
     pub fn new(future: F, timeout: Duration) -> Self {
         Self {
-            future: tokio::time::timeout(timeout, future),
+            future,
+            sleep: rt::sleep(timeout),
+            probe: Probe::timeout(timeout),
         }
     }
 }
@@ -35,13 +54,16 @@ impl<F: Future> Future for Timeout<F> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        let future: Pin<&mut tokio::time::Timeout<F>> = this.future;
+        let future: Pin<&mut F> = this.future;
+        let sleep: Pin<&mut Sleep> = this.sleep;
+        let probe: &Probe = this.probe;
+        let _entered = probe.enter();
 
-        if let Poll::Ready(result) = future.poll(cx) {
-            match result {
-                Ok(out) => Poll::Ready(Ok(out)),
-                Err(_) => Poll::Ready(Err(TimedOut(()))),
-            }
+        if let Poll::Ready(out) = future.poll(cx) {
+            Poll::Ready(Ok(out))
+        } else if sleep.poll(cx).is_ready() {
+            probe.fired(Interrupt::Timeout);
+            Poll::Ready(Err(TimedOut(Interrupt::Timeout)))
         } else {
             Poll::Pending
         }
@@ -52,14 +74,19 @@ impl<F: Future> Future for Timeout<F> {
 #[pin_project]
 pub struct Deadline<F> {
     #[pin]
-    future: tokio::time::Timeout<F>,
+    future: F,
+    #[pin]
+    sleep: Sleep,
+    probe: Probe,
 }
 
 impl<F: Future> Deadline<F> {
     /// Create a new `Deadline` future.
     pub fn new(future: F, deadline: Instant) -> Self {
         Self {
-            future: tokio::time::timeout_at(deadline.into(), future),
+            future,
+            sleep: rt::sleep_until(deadline),
+            probe: Probe::deadline(deadline),
         }
     }
 }
@@ -69,13 +96,16 @@ impl<F: Future> Future for Deadline<F> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        let future: Pin<&mut tokio::time::Timeout<F>> = this.future;
+        let future: Pin<&mut F> = this.future;
+        let sleep: Pin<&mut Sleep> = this.sleep;
+        let probe: &Probe = this.probe;
+        let _entered = probe.enter();
 
-        if let Poll::Ready(result) = future.poll(cx) {
-            match result {
-                Ok(out) => Poll::Ready(Ok(out)),
-                Err(_) => Poll::Ready(Err(TimedOut(()))),
-            }
+        if let Poll::Ready(out) = future.poll(cx) {
+            Poll::Ready(Ok(out))
+        } else if sleep.poll(cx).is_ready() {
+            probe.fired(Interrupt::Deadline);
+            Poll::Ready(Err(TimedOut(Interrupt::Deadline)))
         } else {
             Poll::Pending
         }